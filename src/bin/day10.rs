@@ -1,166 +1,758 @@
-use std::io::{self, Read};
+use std::{
+  collections::HashMap,
+  env, fmt, fs,
+  io::{self, Read, Write},
+};
 
 use itertools::Itertools;
 
 #[derive(Debug)]
 enum Error {
   Incomplete(Vec<Chunk>),
-  Corrupted(char),
+  Corrupted(Corruption),
 }
 
-fn is_closing_char_for(opening_char: char, closing_char: char) -> bool {
-  matches!(
-    (opening_char, closing_char),
-    ('(', ')') | ('[', ']') | ('{', '}') | ('<', '>')
-  )
+/// Where and why a line is corrupted. Lines and columns start at 1, columns count chars.
+#[derive(Debug, Clone, PartialEq)]
+struct Corruption {
+  line: usize,
+  column: usize,
+  found: char,
+  // Whether `found` belongs to the bracket set at all
+  is_delimiter: bool,
+  // Closing char of the innermost open chunk and the column it was opened at, if any is open
+  expected: Option<char>,
+  opened_at: Option<usize>,
 }
 
-fn is_opening_char(ch: char) -> bool {
-  matches!(ch, '(' | '[' | '{' | '<')
+impl Corruption {
+  /// Renders the error like rustc does, pointing at the offending char in `source_line`.
+  fn render(&self, source_line: &str) -> String {
+    let gutter = " ".repeat(self.line.to_string().len());
+    // Blanks lining up with the source chars from column `from` up to `to`, keeping tabs so
+    // markers stay aligned however wide the terminal draws them
+    let blanks = |from: usize, to: usize| {
+      source_line
+        .chars()
+        .skip(from - 1)
+        .take(to - from)
+        .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+        .collect::<String>()
+    };
+    let pad = |column: usize| blanks(1, column);
+    let mut lines = vec![];
+    match (self.expected, self.opened_at) {
+      _ if !self.is_delimiter => {
+        lines.push(format!("error: unknown character `{}`", self.found));
+        lines.push(format!("{}--> {}:{}", gutter, self.line, self.column));
+        lines.push(format!("{} |", gutter));
+        lines.push(format!("{} | {}", self.line, source_line));
+        lines.push(format!(
+          "{} | {}^ not a delimiter",
+          gutter,
+          pad(self.column)
+        ));
+      }
+      (Some(expected), Some(opened_at)) => {
+        lines.push(format!(
+          "error: mismatched closing delimiter `{}`",
+          self.found
+        ));
+        lines.push(format!("{}--> {}:{}", gutter, self.line, self.column));
+        lines.push(format!("{} |", gutter));
+        lines.push(format!("{} | {}", self.line, source_line));
+        lines.push(format!(
+          "{} | {}-{}^ expected `{}`",
+          gutter,
+          pad(opened_at),
+          blanks(opened_at + 1, self.column),
+          expected
+        ));
+        lines.push(format!("{} | {}|", gutter, pad(opened_at)));
+        lines.push(format!("{} | {}unclosed delimiter", gutter, pad(opened_at)));
+      }
+      _ => {
+        lines.push(format!(
+          "error: unexpected closing delimiter `{}`",
+          self.found
+        ));
+        lines.push(format!("{}--> {}:{}", gutter, self.line, self.column));
+        lines.push(format!("{} |", gutter));
+        lines.push(format!("{} | {}", self.line, source_line));
+        lines.push(format!(
+          "{} | {}^ unexpected closing delimiter",
+          gutter,
+          pad(self.column)
+        ));
+      }
+    }
+    lines.join("\n")
+  }
 }
 
-fn closing_char_for(opening_char: char) -> char {
-  match opening_char {
-    '(' => ')',
-    '[' => ']',
-    '{' => '}',
-    '<' => '>',
-    _ => panic!("Invalid opening char '{}'", opening_char),
-  }
+#[derive(Debug, Clone, PartialEq)]
+struct BracketPair {
+  opening_char: char,
+  closing_char: char,
+  illegal_points: i32,
+  auto_complete_score: u128,
+}
+
+/// The delimiters a checker understands, with what each scores when it's illegal or completed.
+#[derive(Debug, Clone, PartialEq)]
+struct BracketSet {
+  pairs: Vec<BracketPair>,
 }
 
-fn points_for_illegal(closing_char: char) -> i32 {
-  match closing_char {
-    ')' => 3,
-    ']' => 57,
-    '}' => 1197,
-    '>' => 25137,
-    _ => 0,
+impl Default for BracketSet {
+  fn default() -> BracketSet {
+    BracketSet::from_config(
+      "( ) 3 1
+[ ] 57 2
+{ } 1197 3
+< > 25137 4",
+    )
+    .unwrap()
   }
 }
 
-fn auto_complete_score(closing_char: char) -> i64 {
-  match closing_char {
-    ')' => 1,
-    ']' => 2,
-    '}' => 3,
-    '>' => 4,
-    _ => 0,
+impl BracketSet {
+  /// One pair per line: opening char, closing char, illegal points and auto-complete score.
+  /// Blank lines and lines starting with `#` are ignored.
+  fn from_config(config: &str) -> Result<BracketSet, String> {
+    let pairs = config
+      .lines()
+      .map(str::trim)
+      .filter(|line| !line.is_empty() && !line.starts_with('#'))
+      .map(|line| {
+        let fields = line.split_whitespace().collect_vec();
+        let single_char = |field: &str| {
+          field
+            .chars()
+            .exactly_one()
+            .map_err(|_| format!("Expected a single char, found '{}' in '{}'", field, line))
+        };
+        match fields[..] {
+          [opening, closing, illegal_points, auto_complete_score] => Ok(BracketPair {
+            opening_char: single_char(opening)?,
+            closing_char: single_char(closing)?,
+            illegal_points: illegal_points
+              .parse()
+              .map_err(|err| format!("Invalid points in '{}': {}", line, err))?,
+            auto_complete_score: auto_complete_score
+              .parse()
+              .map_err(|err| format!("Invalid score in '{}': {}", line, err))?,
+          }),
+          _ => Err(format!("Expected 4 fields in '{}'", line)),
+        }
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    if pairs.is_empty() {
+      return Err("No bracket pairs configured".to_owned());
+    }
+    if let Some(duplicate) = pairs
+      .iter()
+      .map(|pair| pair.opening_char)
+      .duplicates()
+      .next()
+    {
+      return Err(format!("'{}' opens more than one pair", duplicate));
+    }
+    // Scores are looked up by closing char, so it has to identify the pair as well
+    if let Some(duplicate) = pairs
+      .iter()
+      .map(|pair| pair.closing_char)
+      .duplicates()
+      .next()
+    {
+      return Err(format!("'{}' closes more than one pair", duplicate));
+    }
+    Ok(BracketSet { pairs })
+  }
+
+  fn is_closing_char_for(&self, opening_char: char, closing_char: char) -> bool {
+    self
+      .pairs
+      .iter()
+      .any(|pair| pair.opening_char == opening_char && pair.closing_char == closing_char)
+  }
+
+  fn is_closing_char(&self, ch: char) -> bool {
+    self.pairs.iter().any(|pair| pair.closing_char == ch)
+  }
+
+  fn is_opening_char(&self, ch: char) -> bool {
+    self.pairs.iter().any(|pair| pair.opening_char == ch)
+  }
+
+  fn closing_char_for(&self, opening_char: char) -> char {
+    self
+      .pairs
+      .iter()
+      .find(|pair| pair.opening_char == opening_char)
+      .map(|pair| pair.closing_char)
+      .unwrap_or_else(|| panic!("Invalid opening char '{}'", opening_char))
+  }
+
+  fn points_for_illegal(&self, closing_char: char) -> i32 {
+    self
+      .pairs
+      .iter()
+      .find(|pair| pair.closing_char == closing_char)
+      .map_or(0, |pair| pair.illegal_points)
+  }
+
+  fn auto_complete_score(&self, closing_char: char) -> u128 {
+    self
+      .pairs
+      .iter()
+      .find(|pair| pair.closing_char == closing_char)
+      .map_or(0, |pair| pair.auto_complete_score)
   }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 struct Chunk {
   opening_char: char,
+  column: usize,
   closed: bool,
   children: Vec<Chunk>,
 }
 
 impl Chunk {
-  fn new(opening_char: char) -> Chunk {
+  fn new(opening_char: char, column: usize) -> Chunk {
     Chunk {
       opening_char,
+      column,
       closed: false,
       children: Vec::new(),
     }
   }
 
-  fn parse<'a>(&mut self, input: &'a str) -> Result<&'a str, Error> {
-    let mut input = input;
-    while let Some(ch) = input.chars().next() {
-      if is_opening_char(ch) {
-        let mut child = Chunk::new(ch);
-        input = child.parse(&input[1..]).map_err(|err| match err {
-          Error::Incomplete(mut chunks) => {
-            chunks.push(self.clone());
-            Error::Incomplete(chunks)
-          }
-          _ => err,
-        })?;
-        child.close();
-        self.children.push(child);
-      } else if is_closing_char_for(self.opening_char, ch) || ch == '\n' {
-        return Ok(&input[1..]);
-      } else {
-        return Err(Error::Corrupted(ch));
-      }
+  fn close(&mut self) {
+    self.closed = true;
+  }
+
+  /// Number of nesting levels in this subtree, 1 for a chunk without children.
+  fn depth(&self) -> usize {
+    walk(std::slice::from_ref(self))
+      .filter_map(|visit| match visit {
+        Visit::Enter(_, depth) => Some(depth + 1),
+        Visit::Leave => None,
+      })
+      .max()
+      .unwrap_or(0)
+  }
+
+  /// Number of chunks in this subtree, including this one.
+  fn size(&self) -> usize {
+    walk(std::slice::from_ref(self))
+      .filter(|visit| matches!(visit, Visit::Enter(..)))
+      .count()
+  }
+}
+
+/// A step of a depth-first walk over chunk trees. Entering a chunk gives its nesting depth.
+enum Visit<'a> {
+  Enter(&'a Chunk, usize),
+  Leave,
+}
+
+/// Walks the trees rooted at `roots` depth first without recursing, so arbitrarily deep lines
+/// can be traversed.
+fn walk(roots: &[Chunk]) -> impl Iterator<Item = Visit<'_>> {
+  let mut stack = roots
+    .iter()
+    .rev()
+    .map(|chunk| (chunk, 0, false))
+    .collect_vec();
+  std::iter::from_fn(move || {
+    let (chunk, depth, entered) = stack.pop()?;
+    if entered {
+      return Some(Visit::Leave);
+    }
+    stack.push((chunk, depth, true));
+    stack.extend(
+      chunk
+        .children
+        .iter()
+        .rev()
+        .map(|child| (child, depth + 1, false)),
+    );
+    Some(Visit::Enter(chunk, depth))
+  })
+}
+
+/// Child indices leading from `roots` to the chunk opened at `column`, if any.
+fn path_to(roots: &[Chunk], column: usize) -> Option<Vec<usize>> {
+  let mut path = vec![];
+  let mut siblings = roots;
+  loop {
+    // Chunks are ordered by column, so the target is inside the last one opened before it
+    let idx = siblings.iter().rposition(|chunk| chunk.column <= column)?;
+    path.push(idx);
+    if siblings[idx].column == column {
+      return Some(path);
     }
-    Err(Error::Incomplete(vec![self.clone()]))
+    siblings = &siblings[idx].children;
   }
+}
 
-  fn close(&mut self) {
-    self.closed = true;
+/// Renders the chunk trees as an indented outline, one chunk per line with its column.
+fn pretty_print(roots: &[Chunk], brackets: &BracketSet) -> String {
+  walk(roots)
+    .filter_map(|visit| match visit {
+      Visit::Enter(chunk, depth) => Some(format!(
+        "{}{}{} @{}",
+        "  ".repeat(depth),
+        chunk.opening_char,
+        if chunk.closed {
+          brackets.closing_char_for(chunk.opening_char).to_string()
+        } else {
+          String::new()
+        },
+        chunk.column
+      )),
+      Visit::Leave => None,
+    })
+    .join("\n")
+}
+
+fn json_char(ch: char) -> String {
+  match ch {
+    '"' | '\\' => format!("\"\\{}\"", ch),
+    _ => format!("\"{}\"", ch),
   }
 }
 
-fn parse(input: &str) -> Vec<Result<Vec<Chunk>, Error>> {
-  input.split('\n').fold(Vec::new(), |mut acc, line| {
-    let mut chunks = Vec::<Chunk>::new();
-    let mut line_remainder = line;
-    println!("'{}'", line_remainder);
-    while let Some(opening_char) = line_remainder.chars().next() {
-      let mut root = Chunk::new(opening_char);
-      match root.parse(&line_remainder[1..]) {
-        Ok(remainder) => {
-          chunks.push(root);
-          line_remainder = remainder;
+/// Exports the chunk trees as a JSON array of `{"open", "close", "column", "children"}` objects,
+/// with `close` null for chunks that were never closed.
+fn to_json(roots: &[Chunk], brackets: &BracketSet) -> String {
+  let mut json = String::from("[");
+  let mut first = true;
+  for visit in walk(roots) {
+    match visit {
+      Visit::Enter(chunk, _) => {
+        if !first {
+          json.push(',');
         }
-        Err(error) => {
-          acc.push(Err(error));
-          return acc;
+        let close = if chunk.closed {
+          json_char(brackets.closing_char_for(chunk.opening_char))
+        } else {
+          "null".to_string()
+        };
+        json.push_str(&format!(
+          "{{\"open\":{},\"close\":{},\"column\":{},\"children\":[",
+          json_char(chunk.opening_char),
+          close,
+          chunk.column
+        ));
+        first = true;
+      }
+      Visit::Leave => {
+        json.push_str("]}");
+        first = false;
+      }
+    }
+  }
+  json.push(']');
+  json
+}
+
+impl Drop for Chunk {
+  fn drop(&mut self) {
+    // Flatten the tree first, dropping deeply nested chunks recursively overflows the stack
+    let mut descendants = std::mem::take(&mut self.children);
+    while let Some(mut chunk) = descendants.pop() {
+      descendants.append(&mut chunk.children);
+    }
+  }
+}
+
+/// Parses a line into its top-level chunks, keeping the chunks that are still open on a stack.
+///
+/// An incomplete line returns the open chunks innermost first, so their closing chars are the
+/// completion of the line. Whitespace is ignored but still counts towards columns.
+fn parse_line(line_number: usize, line: &str, brackets: &BracketSet) -> Result<Vec<Chunk>, Error> {
+  let mut chunks = Vec::new();
+  let mut open: Vec<Chunk> = Vec::new();
+  for (idx, ch) in line.chars().enumerate() {
+    let column = idx + 1;
+    if ch.is_whitespace() {
+      continue;
+    }
+    // Closing is checked first so pairs like quotes, that open and close with the same char,
+    // close the innermost chunk instead of nesting forever
+    let closes_innermost = open
+      .last()
+      .is_some_and(|chunk| brackets.is_closing_char_for(chunk.opening_char, ch));
+    if !closes_innermost {
+      if brackets.is_opening_char(ch) {
+        open.push(Chunk::new(ch, column));
+        continue;
+      }
+      let innermost = open.last();
+      return Err(Error::Corrupted(Corruption {
+        line: line_number,
+        column,
+        found: ch,
+        is_delimiter: brackets.is_closing_char(ch),
+        expected: innermost.map(|chunk| brackets.closing_char_for(chunk.opening_char)),
+        opened_at: innermost.map(|chunk| chunk.column),
+      }));
+    }
+    let mut chunk = open.pop().unwrap();
+    chunk.close();
+    match open.last_mut() {
+      Some(parent) => parent.children.push(chunk),
+      None => chunks.push(chunk),
+    }
+  }
+  if open.is_empty() {
+    Ok(chunks)
+  } else {
+    open.reverse();
+    Err(Error::Incomplete(open))
+  }
+}
+
+/// The non-blank lines of `input` with their 1-based line numbers. Both LF and CRLF endings are
+/// accepted, and blank lines still count so numbers match the original input.
+fn source_lines(input: &str) -> impl Iterator<Item = (usize, &str)> {
+  input
+    .lines()
+    .enumerate()
+    .map(|(idx, line)| (idx + 1, line))
+    .filter(|(_, line)| !line.trim().is_empty())
+}
+
+fn parse(input: &str, brackets: &BracketSet) -> Vec<Result<Vec<Chunk>, Error>> {
+  source_lines(input)
+    .map(|(line_number, line)| parse_line(line_number, line, brackets))
+    .collect()
+}
+
+/// A single fix to a line. Columns start at 1 and count chars; an insertion goes before the
+/// char at `column`, so `line.len() + 1` appends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Edit {
+  Insert { column: usize, ch: char },
+  Delete { column: usize, ch: char },
+}
+
+impl Edit {
+  fn column(&self) -> usize {
+    match self {
+      Edit::Insert { column, .. } | Edit::Delete { column, .. } => *column,
+    }
+  }
+}
+
+impl fmt::Display for Edit {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Edit::Insert { column, ch } => write!(f, "{}: insert `{}`", column, ch),
+      Edit::Delete { column, ch } => write!(f, "{}: delete `{}`", column, ch),
+    }
+  }
+}
+
+/// How the range starting at some char was made balanced.
+#[derive(Clone, Copy)]
+enum Fix {
+  // The first char is deleted, or for an opener its closer is inserted at the range end
+  Edit,
+  // The first char is whitespace and left as is
+  Skip,
+  // The first char is an opener matched by the closer at this index
+  Match(usize),
+}
+
+/// Finds a minimal set of edits that makes `line` fully valid, reporting every error in it
+/// rather than only the first. Unclosed openers get their closer inserted where their chunk
+/// ends and stray closers are deleted. Runs in cubic time and quadratic memory in the line
+/// length, see `recover` for long lines.
+fn minimal_edits(line: &str, brackets: &BracketSet) -> Vec<Edit> {
+  let chars = line.chars().collect_vec();
+  let n = chars.len();
+  // cost[i][j] is the fewest edits that balance chars[i..j]
+  let mut cost = vec![vec![0usize; n + 1]; n + 1];
+  let mut fix = vec![vec![Fix::Edit; n + 1]; n + 1];
+  for len in 1..=n {
+    for i in 0..=n - len {
+      let j = i + len;
+      if chars[i].is_whitespace() {
+        cost[i][j] = cost[i + 1][j];
+        fix[i][j] = Fix::Skip;
+        continue;
+      }
+      let mut best = (cost[i + 1][j] + 1, Fix::Edit);
+      if brackets.is_opening_char(chars[i]) {
+        for k in i + 1..j {
+          if brackets.is_closing_char_for(chars[i], chars[k]) {
+            let candidate = cost[i + 1][k] + cost[k + 1][j];
+            if candidate < best.0 {
+              best = (candidate, Fix::Match(k));
+            }
+          }
         }
       }
+      cost[i][j] = best.0;
+      fix[i][j] = best.1;
     }
-    acc.push(Ok(chunks));
-    acc
-  })
+  }
+
+  // Walk the chosen fixes, tracking how deeply each insertion is nested so that insertions
+  // sharing a column can be ordered innermost first
+  let mut edits = vec![];
+  let mut ranges = vec![(0, n, 0)];
+  while let Some((i, j, depth)) = ranges.pop() {
+    if i >= j {
+      continue;
+    }
+    match fix[i][j] {
+      Fix::Edit if brackets.is_opening_char(chars[i]) => {
+        let ch = brackets.closing_char_for(chars[i]);
+        edits.push((Edit::Insert { column: j + 1, ch }, depth));
+        ranges.push((i + 1, j, depth + 1));
+      }
+      Fix::Edit => {
+        edits.push((
+          Edit::Delete {
+            column: i + 1,
+            ch: chars[i],
+          },
+          depth,
+        ));
+        ranges.push((i + 1, j, depth));
+      }
+      Fix::Skip => ranges.push((i + 1, j, depth)),
+      Fix::Match(k) => {
+        ranges.push((i + 1, k, depth + 1));
+        ranges.push((k + 1, j, depth));
+      }
+    }
+  }
+  edits.sort_by_key(|(edit, depth)| {
+    (
+      edit.column(),
+      matches!(edit, Edit::Delete { .. }),
+      usize::MAX - depth,
+    )
+  });
+  edits.into_iter().map(|(edit, _)| edit).collect()
+}
+
+/// Fallback for lines too long for `minimal_edits`: resynchronizes after every error instead of
+/// stopping at the first, returning edits that make `line` valid but may not be the fewest. A closer that doesn't match the innermost chunk closes the nearest open
+/// chunk it does match, inserting the closers of the chunks in between, and is deleted if no
+/// open chunk matches it. Other stray chars are deleted and chunks still open at the end get
+/// their closers appended. Runs in time linear in the line length.
+fn resync(line: &str, brackets: &BracketSet) -> Vec<Edit> {
+  let mut edits = vec![];
+  // Open chunks as (opening char, closing char), and how many of them each closing char closes
+  let mut open: Vec<(char, char)> = vec![];
+  let mut open_closers: HashMap<char, usize> = HashMap::new();
+  let mut num_chars = 0;
+  for (idx, ch) in line.chars().enumerate() {
+    let column = idx + 1;
+    num_chars = column;
+    if ch.is_whitespace() {
+      continue;
+    }
+    let closes_innermost = open
+      .last()
+      .is_some_and(|(opening_char, _)| brackets.is_closing_char_for(*opening_char, ch));
+    if !closes_innermost && brackets.is_opening_char(ch) {
+      let closing_char = brackets.closing_char_for(ch);
+      open.push((ch, closing_char));
+      *open_closers.entry(closing_char).or_default() += 1;
+      continue;
+    }
+    if !closes_innermost && open_closers.get(&ch).copied().unwrap_or(0) == 0 {
+      edits.push(Edit::Delete { column, ch });
+      continue;
+    }
+    // Close every chunk nested inside the nearest one this char closes, then that chunk itself
+    while let Some((opening_char, closing_char)) = open.pop() {
+      *open_closers.get_mut(&closing_char).unwrap() -= 1;
+      if brackets.is_closing_char_for(opening_char, ch) {
+        break;
+      }
+      edits.push(Edit::Insert {
+        column,
+        ch: closing_char,
+      });
+    }
+  }
+  edits.extend(open.iter().rev().map(|(_, closing_char)| Edit::Insert {
+    column: num_chars + 1,
+    ch: *closing_char,
+  }));
+  edits
+}
+
+// At 500 chars the minimal search takes about 20 million steps and two 250k entry tables
+const MAX_MINIMAL_RECOVERY_LEN: usize = 500;
+
+/// Edits that make a line valid, and whether they are known to be the fewest possible.
+#[derive(Debug, PartialEq)]
+struct Recovery {
+  edits: Vec<Edit>,
+  minimal: bool,
+}
+
+/// Minimal edits for lines up to `MAX_MINIMAL_RECOVERY_LEN` chars, longer lines fall back to the
+/// linear `resync`.
+fn recover(line: &str, brackets: &BracketSet) -> Recovery {
+  if line.chars().count() <= MAX_MINIMAL_RECOVERY_LEN {
+    Recovery {
+      edits: minimal_edits(line, brackets),
+      minimal: true,
+    }
+  } else {
+    Recovery {
+      edits: resync(line, brackets),
+      minimal: false,
+    }
+  }
+}
+
+/// Applies edits as returned by `recover` to `line`.
+fn apply_edits(line: &str, edits: &[Edit]) -> String {
+  let mut fixed = String::new();
+  let mut pending = edits.iter().peekable();
+  for (idx, ch) in line.chars().enumerate() {
+    let mut keep = true;
+    while let Some(edit) = pending.next_if(|edit| edit.column() == idx + 1) {
+      match edit {
+        Edit::Insert { ch, .. } => fixed.push(*ch),
+        Edit::Delete { .. } => keep = false,
+      }
+    }
+    if keep {
+      fixed.push(ch);
+    }
+  }
+  for edit in pending {
+    if let Edit::Insert { ch, .. } = edit {
+      fixed.push(*ch);
+    }
+  }
+  fixed
 }
 
 #[allow(dead_code)]
-fn part_1(input: &str) {
-  let parsed = parse(input);
+fn part_1(input: &str, brackets: &BracketSet) -> i32 {
+  let parsed = parse(input, brackets);
   let points: i32 = parsed
     .iter()
     .map(|result| match result {
       Ok(_) => 0,
       Err(Error::Incomplete(_)) => 0,
-      Err(Error::Corrupted(ch)) => points_for_illegal(*ch),
+      Err(Error::Corrupted(corruption)) => brackets.points_for_illegal(corruption.found),
     })
     .sum();
   println!("Points: {}", points);
+  points
 }
 
-fn part_2(input: &str) {
-  let parsed = parse(input);
-  let auto_completed = parsed
-    .iter()
-    .filter_map(|result| match result {
-      Ok(_) | Err(Error::Corrupted(_)) => None,
-      Err(Error::Incomplete(chunks)) => Some(chunks),
-    })
-    .fold(Vec::new(), |mut acc, incomplete| {
-      let to_complete = incomplete
-        .iter()
-        .fold(String::new(), |mut complete_acc, chunk| {
-          complete_acc.push(closing_char_for(chunk.opening_char));
-          complete_acc
-        });
-      acc.push(to_complete);
-      acc
-    });
-  let points = auto_completed
+/// The result of checking a single line.
+#[derive(Debug, PartialEq)]
+enum Status {
+  Valid,
+  // The score is None when it does not fit in a u128
+  Incomplete {
+    completion: String,
+    score: Option<u128>,
+  },
+  Corrupted(Corruption),
+}
+
+impl fmt::Display for Status {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Status::Valid => write!(f, "valid"),
+      Status::Incomplete { completion, score } => match score {
+        Some(score) => write!(
+          f,
+          "incomplete, complete with `{}` (score {})",
+          completion, score
+        ),
+        None => write!(
+          f,
+          "incomplete, complete with `{}` (score overflows)",
+          completion
+        ),
+      },
+      Status::Corrupted(corruption) => write!(
+        f,
+        "corrupted, unexpected `{}` at column {}",
+        corruption.found, corruption.column
+      ),
+    }
+  }
+}
+
+/// The closing chars that complete the given open chunks, innermost first.
+fn completion_for(open: &[Chunk], brackets: &BracketSet) -> String {
+  open
     .iter()
-    .map(|auto_completed| {
-      auto_completed
-        .chars()
-        .fold(0, |acc, ch| (acc * 5) + auto_complete_score(ch))
+    .map(|chunk| brackets.closing_char_for(chunk.opening_char))
+    .collect()
+}
+
+/// Scores a completion, or None if the score overflows a u128.
+fn completion_score(completion: &str, brackets: &BracketSet) -> Option<u128> {
+  completion.chars().try_fold(0u128, |acc, ch| {
+    acc
+      .checked_mul(5)?
+      .checked_add(brackets.auto_complete_score(ch))
+  })
+}
+
+/// The middle of the sorted `points`, which must have an odd length so the middle is unique.
+fn median(points: &[u128]) -> Result<u128, String> {
+  if points.len().is_multiple_of(2) {
+    return Err(format!(
+      "Expected an odd number of scores to take the median of, got {}",
+      points.len()
+    ));
+  }
+  Ok(points[points.len() / 2])
+}
+
+/// Checks `prefix` and, if it is incomplete, returns the chars that complete it.
+fn complete(prefix: &str, brackets: &BracketSet) -> Status {
+  match parse_line(1, prefix, brackets) {
+    Ok(_) => Status::Valid,
+    Err(Error::Incomplete(open)) => {
+      let completion = completion_for(&open, brackets);
+      let score = completion_score(&completion, brackets);
+      Status::Incomplete { completion, score }
+    }
+    Err(Error::Corrupted(corruption)) => Status::Corrupted(corruption),
+  }
+}
+
+fn part_2(input: &str, brackets: &BracketSet) -> Result<u128, String> {
+  let points = source_lines(input)
+    .filter_map(|(line_number, line)| match complete(line, brackets) {
+      Status::Incomplete { score, .. } => {
+        Some(score.ok_or_else(|| format!("Completion score of line {} overflows", line_number)))
+      }
+      Status::Valid | Status::Corrupted(_) => None,
     })
+    .collect::<Result<Vec<_>, _>>()?
+    .into_iter()
     .sorted_unstable()
     .collect_vec();
 
-  let middle = points[points.len() / 2];
+  let middle = median(&points)?;
 
   println!("Middle {}", middle);
+  Ok(middle)
 }
 
 #[test]
@@ -175,7 +767,7 @@ fn test_part_1() {
 [<(<(<(<{}))><([]([]()
 <{([([[(<>()){}]>(<<{{
 <{([{{}}[<[[[<>{}]]]>[]]";
-  part_1(input);
+  assert_eq!(part_1(input, &BracketSet::default()), 26397);
 }
 
 #[test]
@@ -190,13 +782,468 @@ fn test_part_2() {
 [<(<(<(<{}))><([]([]()
 <{([([[(<>()){}]>(<<{{
 <{([{{}}[<[[[<>{}]]]>[]]";
-  part_2(input);
-  // )}>]})
-  // )}>]})
+  assert_eq!(part_2(input, &BracketSet::default()), Ok(288957));
+}
+
+#[test]
+fn test_parse_deeply_nested() {
+  let brackets = BracketSet::default();
+  let depth = 1_000_000;
+  let valid = "(".repeat(depth) + &")".repeat(depth);
+  let chunks = parse_line(1, &valid, &brackets).unwrap();
+  assert_eq!(chunks.len(), 1);
+  assert!(chunks[0].closed);
+
+  let incomplete = "[".repeat(depth) + "()";
+  match parse_line(1, &incomplete, &brackets) {
+    Err(Error::Incomplete(open)) => {
+      assert_eq!(open.len(), depth);
+      assert_eq!(open[0].children.len(), 1);
+    }
+    other => panic!(
+      "Expected incomplete line, got {:?}",
+      other.map(|chunks| chunks.len())
+    ),
+  }
+
+  assert!(matches!(
+    parse_line(1, "(]", &brackets),
+    Err(Error::Corrupted(Corruption { found: ']', .. }))
+  ));
+  assert!(matches!(
+    parse_line(1, ")", &brackets),
+    Err(Error::Corrupted(Corruption { found: ')', .. }))
+  ));
+}
+
+#[test]
+fn test_corruption_diagnostics() {
+  let input = "[({(<(())[]>[[{[]{<()<>>
+[(()[<>])]({[<{<<[]>>(
+{([(<{}[<>[]}>{[]{[(<()>
+)";
+  let parsed = parse(input, &BracketSet::default());
+  let corruptions = parsed
+    .iter()
+    .filter_map(|result| match result {
+      Err(Error::Corrupted(corruption)) => Some(corruption),
+      _ => None,
+    })
+    .collect_vec();
+  assert_eq!(
+    corruptions,
+    [
+      &Corruption {
+        line: 3,
+        column: 13,
+        found: '}',
+        is_delimiter: true,
+        expected: Some(']'),
+        opened_at: Some(8),
+      },
+      &Corruption {
+        line: 4,
+        column: 1,
+        found: ')',
+        is_delimiter: true,
+        expected: None,
+        opened_at: None,
+      }
+    ]
+  );
+  assert_eq!(
+    corruptions[0].render("{([(<{}[<>[]}>{[]{[(<()>"),
+    "error: mismatched closing delimiter `}`
+ --> 3:13
+  |
+3 | {([(<{}[<>[]}>{[]{[(<()>
+  |        -    ^ expected `]`
+  |        |
+  |        unclosed delimiter"
+  );
+  assert_eq!(
+    corruptions[1].render(")"),
+    "error: unexpected closing delimiter `)`
+ --> 4:1
+  |
+4 | )
+  | ^ unexpected closing delimiter"
+  );
+
+  let brackets = BracketSet::default();
+  let corruption = |line: &str| match parse_line(1, line, &brackets) {
+    Err(Error::Corrupted(corruption)) => corruption,
+    _ => panic!("Expected {} to be corrupted", line),
+  };
+  assert_eq!(
+    corruption("(a)").render("(a)"),
+    "error: unknown character `a`
+ --> 1:2
+  |
+1 | (a)
+  |  ^ not a delimiter"
+  );
+  // Tabs are copied into the padding so the markers line up under the source
+  assert_eq!(
+    corruption("\t(\t]").render("\t(\t]"),
+    "error: mismatched closing delimiter `]`
+ --> 1:4
+  |
+1 | \t(\t]
+  | \t-\t^ expected `)`
+  | \t|
+  | \tunclosed delimiter"
+  );
+}
+
+#[test]
+fn test_recover() {
+  let brackets = BracketSet::default();
+  assert_eq!(
+    recover("(()))", &brackets),
+    Recovery {
+      edits: vec![Edit::Delete { column: 5, ch: ')' }],
+      minimal: true
+    }
+  );
+  // Deleting the stray `]` and its partner beats closing the four `(` and dropping their closers
+  let recovery = recover("[((((]))))", &brackets);
+  assert!(recovery.minimal);
+  assert_eq!(recovery.edits.len(), 2);
+  assert_eq!(recover("(]a)>", &brackets).edits.len(), 3);
+  // Incomplete lines are fixed by appending their auto-completion
+  let incomplete = "[({(<(())[]>[[{[]{<()<>>";
+  assert_eq!(
+    apply_edits(incomplete, &recover(incomplete, &brackets).edits),
+    format!("{}}}}}]])}})]", incomplete)
+  );
+  let input = "[({(<(())[]>[[{[]{<()<>>
+[(()[<>])]({[<{<<[]>>(
+{([(<{}[<>[]}>{[]{[(<()>
+(((({<>}<{<{<>}{[]{[]{}
+[[<[([]))<([[{}[[()]]]
+[{[{({}]{}}([{[{{{}}([]
+{<[[]]>}<{[{[{[]{()[[[]
+[<(<(<(<{}))><([]([]()
+<{([([[(<>()){}]>(<<{{
+<{([{{}}[<[[[<>{}]]]>[]]";
+  for line in input.split('\n') {
+    let edits = recover(line, &brackets).edits;
+    let resynced = resync(line, &brackets);
+    for edits in [&edits, &resynced] {
+      let fixed = apply_edits(line, edits);
+      assert!(
+        parse_line(1, &fixed, &brackets).is_ok(),
+        "{} -> {}",
+        line,
+        fixed
+      );
+    }
+    assert!(edits.len() <= resynced.len());
+    assert_eq!(edits.is_empty(), parse_line(1, line, &brackets).is_ok());
+  }
+}
+
+#[test]
+fn test_resync() {
+  let brackets = BracketSet::default();
+  // The stray closer closes the `[` around it, so the `(` gets closed first
+  assert_eq!(
+    resync("[(])", &brackets),
+    [
+      Edit::Insert { column: 3, ch: ')' },
+      Edit::Delete { column: 4, ch: ')' }
+    ]
+  );
+  // Every error in the line is reported, not only the first
+  assert_eq!(
+    resync("(]a)>", &brackets),
+    [
+      Edit::Delete { column: 2, ch: ']' },
+      Edit::Delete { column: 3, ch: 'a' },
+      Edit::Delete { column: 5, ch: '>' }
+    ]
+  );
+
+  // Long lines recover in linear time, also when most closers match nothing that is open
+  let depth = 1_000_000;
+  let deep = "(".repeat(depth) + &"]".repeat(depth);
+  let recovery = recover(&deep, &brackets);
+  assert!(!recovery.minimal);
+  let edits = recovery.edits;
+  assert_eq!(edits.len(), 2 * depth);
+  assert_eq!(
+    edits[0],
+    Edit::Delete {
+      column: depth + 1,
+      ch: ']'
+    }
+  );
+  assert_eq!(
+    edits[2 * depth - 1],
+    Edit::Insert {
+      column: 2 * depth + 1,
+      ch: ')'
+    }
+  );
+}
+
+#[test]
+fn test_ast() {
+  let brackets = BracketSet::default();
+  let chunks = parse_line(1, "[<>({}){}[([])<>]]()", &brackets).unwrap();
+  assert_eq!(chunks.len(), 2);
+  assert_eq!(chunks[0].depth(), 4);
+  assert_eq!(chunks[0].size(), 9);
+  assert_eq!(chunks[1].size(), 1);
+  assert_eq!(path_to(&chunks, 12), Some(vec![0, 3, 0, 0]));
+  assert_eq!(path_to(&chunks, 19), Some(vec![1]));
+  assert_eq!(path_to(&chunks, 3), None);
+  assert_eq!(pretty_print(&chunks[1..], &brackets), "() @19");
+  assert_eq!(
+    pretty_print(&parse_line(1, "[<>{}]", &brackets).unwrap(), &brackets),
+    "[] @1\n  <> @2\n  {} @4"
+  );
+  assert_eq!(
+    to_json(&parse_line(1, "[<>]()", &brackets).unwrap(), &brackets),
+    r#"[{"open":"[","close":"]","column":1,"children":[{"open":"<","close":">","column":2,"children":[]}]},{"open":"(","close":")","column":5,"children":[]}]"#
+  );
+
+  let deep = "(".repeat(100_000) + &")".repeat(100_000);
+  let chunks = parse_line(1, &deep, &brackets).unwrap();
+  assert_eq!(chunks[0].depth(), 100_000);
+  assert_eq!(chunks[0].size(), 100_000);
+}
+
+#[test]
+fn test_line_terminators() {
+  let brackets = BracketSet::default();
+  let input = "[({(<(())[]>[[{[]{<()<>>
+[(()[<>])]({[<{<<[]>>(
+{([(<{}[<>[]}>{[]{[(<()>
+(((({<>}<{<{<>}{[]{[]{}
+[[<[([]))<([[{}[[()]]]";
+  let expected = (part_1(input, &brackets), part_2(input, &brackets));
+
+  // CRLF endings
+  let crlf = input.replace('\n', "\r\n");
+  assert_eq!(
+    (part_1(&crlf, &brackets), part_2(&crlf, &brackets)),
+    expected
+  );
+
+  // Trailing blank lines, with or without carriage returns
+  for trailing in ["\n", "\n\n", "\r\n\r\n", "\n  \n"] {
+    let padded = format!("{}{}", input, trailing);
+    assert_eq!(parse(&padded, &brackets).len(), 5);
+    assert_eq!(
+      (part_1(&padded, &brackets), part_2(&padded, &brackets)),
+      expected
+    );
+  }
+
+  // Blank lines are skipped but keep the original line numbers
+  let gapped = "()\n\n \r\n(]";
+  let parsed = parse(gapped, &brackets);
+  assert_eq!(parsed.len(), 2);
+  assert!(matches!(
+    parsed[1],
+    Err(Error::Corrupted(Corruption {
+      line: 4,
+      column: 2,
+      ..
+    }))
+  ));
+
+  // Whitespace inside a line is ignored but counted in columns
+  assert!(parse_line(1, " ( [ ] ) ", &brackets).is_ok());
+  assert!(matches!(
+    parse_line(1, "(\t]", &brackets),
+    Err(Error::Corrupted(Corruption {
+      column: 3,
+      opened_at: Some(1),
+      ..
+    }))
+  ));
+  assert_eq!(
+    recover("( ]", &brackets).edits,
+    [
+      Edit::Delete { column: 3, ch: ']' },
+      Edit::Insert { column: 4, ch: ')' }
+    ]
+  );
+}
+
+#[test]
+fn test_complete() {
+  let brackets = BracketSet::default();
+  assert_eq!(complete("[]<>", &brackets), Status::Valid);
+  assert_eq!(
+    complete("<{([{{}}[<[[[<>{}]]]>[]]", &brackets),
+    Status::Incomplete {
+      completion: "])}>".to_string(),
+      score: Some(294)
+    }
+  );
+  assert!(matches!(
+    complete("{([(<{}[<>[]}>{[]{[(<()>", &brackets),
+    Status::Corrupted(Corruption { found: '}', .. })
+  ));
+}
+
+#[test]
+fn test_completion_score_overflow() {
+  let brackets = BracketSet::default();
+  // 55 closers of score 1 sum to (5^55 - 1) / 4, which still fits in a u128
+  let fits = completion_score(&")".repeat(55), &brackets).unwrap();
+  assert_eq!(fits, (5u128.pow(55) - 1) / 4);
+  assert_eq!(completion_score(&">".repeat(56), &brackets), None);
+
+  let overflowing = format!("()\n{}", "<".repeat(60));
+  assert_eq!(
+    part_2(&overflowing, &brackets),
+    Err("Completion score of line 2 overflows".to_string())
+  );
+  assert!(part_2("(\n[", &brackets).is_err());
+  assert!(part_2("()", &brackets).is_err());
+  assert_eq!(median(&[1, 2, 3]), Ok(2));
+}
+
+#[test]
+fn test_custom_brackets() {
+  let brackets = BracketSet::from_config(
+    "# guillemets and quotes
+« » 10 1
+
+\" \" 20 2
+( ) 30 3",
+  )
+  .unwrap();
+  let input = "«(\"«»\")»
+«\"(\"»
+(«\"
+«)";
+  assert_eq!(part_1(input, &brackets), 10 + 30);
+  // Completing with "») scores ((2 * 5) + 1) * 5 + 3
+  assert_eq!(part_2(input, &brackets), Ok(58));
+  assert!(matches!(
+    parse_line(1, "[]", &brackets),
+    Err(Error::Corrupted(Corruption { found: '[', .. }))
+  ));
+
+  assert!(BracketSet::from_config("( ) 3").is_err());
+  assert!(BracketSet::from_config("() ) 3 1").is_err());
+  assert!(BracketSet::from_config("( ) 3 1\n( ] 5 2").is_err());
+  assert_eq!(
+    BracketSet::from_config("( ) 3 1\n[ ) 57 2").err(),
+    Some("')' closes more than one pair".to_string())
+  );
+  assert!(BracketSet::from_config("# nothing").is_err());
+}
+
+/// Reads lines from stdin and reports the status of each as it is entered.
+fn repl(brackets: &BracketSet) {
+  let mut stdout = io::stdout();
+  loop {
+    print!("> ");
+    stdout.flush().expect("Failed to flush stdout");
+    let mut line = String::new();
+    if io::stdin()
+      .read_line(&mut line)
+      .expect("Failed to read line")
+      == 0
+    {
+      break;
+    }
+    println!(
+      "{}",
+      complete(line.trim_end_matches(['\r', '\n']), brackets)
+    );
+  }
 }
 
 fn main() {
+  let args: Vec<String> = env::args().skip(1).collect();
+  let brackets = match args
+    .iter()
+    .position(|arg| arg == "--brackets")
+    .and_then(|idx| args.get(idx + 1))
+  {
+    Some(path) => {
+      let config = fs::read_to_string(path).expect("Failed to read bracket config");
+      BracketSet::from_config(&config).unwrap_or_else(|err| panic!("{}", err))
+    }
+    None => BracketSet::default(),
+  };
+  if args.iter().any(|arg| arg == "--repl") {
+    repl(&brackets);
+    return;
+  }
   let mut input = String::new();
   let _result = io::stdin().lock().read_to_string(&mut input);
-  part_2(&input);
+  if args.iter().any(|arg| arg == "--lint") {
+    let lines = input.lines().collect_vec();
+    for result in parse(&input, &brackets) {
+      if let Err(Error::Corrupted(corruption)) = result {
+        println!("{}\n", corruption.render(lines[corruption.line - 1]));
+      }
+    }
+    return;
+  }
+  if let Some(format) = args
+    .iter()
+    .position(|arg| arg == "--ast")
+    .and_then(|idx| args.get(idx + 1))
+  {
+    let at = args
+      .iter()
+      .position(|arg| arg == "--at")
+      .and_then(|idx| args.get(idx + 1))
+      .map(|column| column.parse::<usize>().expect("Invalid column"));
+    for (line_number, line) in source_lines(&input) {
+      if let Ok(chunks) = parse_line(line_number, line, &brackets) {
+        println!(
+          "line {}: {} chunks, depth {}",
+          line_number,
+          chunks.iter().map(Chunk::size).sum::<usize>(),
+          chunks.iter().map(Chunk::depth).max().unwrap_or(0)
+        );
+        if let Some(column) = at {
+          println!("path to {}: {:?}", column, path_to(&chunks, column));
+        }
+        match format.as_str() {
+          "pretty" => println!("{}", pretty_print(&chunks, &brackets)),
+          "json" => println!("{}", to_json(&chunks, &brackets)),
+          _ => panic!("Unknown AST format: {}", format),
+        }
+      }
+    }
+    return;
+  }
+  if args.iter().any(|arg| arg == "--recover") {
+    for (line_number, line) in source_lines(&input) {
+      let Recovery { edits, minimal } = recover(line, &brackets);
+      if !edits.is_empty() {
+        let label = if minimal {
+          ""
+        } else {
+          " (resynchronized, may not be minimal)"
+        };
+        println!(
+          "line {}{}: {}",
+          line_number,
+          label,
+          apply_edits(line, &edits)
+        );
+        for edit in edits {
+          println!("  {}", edit);
+        }
+      }
+    }
+    return;
+  }
+  if let Err(err) = part_2(&input, &brackets) {
+    eprintln!("{}", err);
+    std::process::exit(1);
+  }
 }
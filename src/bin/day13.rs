@@ -102,7 +102,95 @@ fn part_1(input: &str) {
   println!("Remaining {:?}", remaining.len());
 }
 
-fn part_2(input: &str) {
+/// The 4x6 font the folded codes are written in, each glyph as its rows joined top to bottom.
+const FONT: [(char, &str); 17] = [
+  ('A', ".##.#..##..######..##..#"),
+  ('B', "###.#..####.#..##..####."),
+  ('C', ".##.#..##...#...#..#.##."),
+  ('E', "#####...###.#...#...####"),
+  ('F', "#####...###.#...#...#..."),
+  ('G', ".##.#..##...#.###..#.###"),
+  ('H', "#..##..######..##..##..#"),
+  ('I', ".###..#...#...#...#..###"),
+  ('J', "..##...#...#...##..#.##."),
+  ('K', "#..##.#.##..#.#.#.#.#..#"),
+  ('L', "#...#...#...#...#...####"),
+  ('O', ".##.#..##..##..##..#.##."),
+  ('P', "###.#..##..####.#...#..."),
+  ('R', "###.#..##..####.#.#.#..#"),
+  ('S', ".####...#....##....####."),
+  ('U', "#..##..##..##..##..#.##."),
+  ('Z', "####...#..#..#..#...####"),
+];
+const GLYPH_WIDTH: i32 = 4;
+const GLYPH_HEIGHT: i32 = 6;
+// Letters are separated by one empty column
+const GLYPH_SPACING: i32 = GLYPH_WIDTH + 1;
+
+/// Reads the letters spelled by the dots, which must start at the origin.
+///
+/// Fails with every unrecognized glyph drawn out, so unknown letters can be added to the font.
+/// Dots in the empty column after a letter make it unrecognized too.
+fn ocr(dots: &HashSet<Dot>) -> Result<String, String> {
+  let max_x = dots
+    .iter()
+    .map(|dot| dot.x)
+    .max()
+    .ok_or("No dots to read")?;
+  let max_y = dots.iter().map(|dot| dot.y).max().unwrap();
+  if dots.iter().any(|dot| dot.x < 0 || dot.y < 0) || max_y >= GLYPH_HEIGHT {
+    return Err(format!(
+      "Expected letters {} dots high starting at the origin",
+      GLYPH_HEIGHT
+    ));
+  }
+  let mut code = String::new();
+  let mut unrecognized = vec![];
+  for idx in 0..=max_x / GLYPH_SPACING {
+    // Read the letter along with its separator column
+    let cells = (0..GLYPH_HEIGHT)
+      .flat_map(|y| (0..GLYPH_SPACING).map(move |x| (x, y)))
+      .map(|(x, y)| {
+        let dot = Dot {
+          x: idx * GLYPH_SPACING + x,
+          y,
+        };
+        if dots.contains(&dot) {
+          '#'
+        } else {
+          '.'
+        }
+      })
+      .collect_vec();
+    let rows = cells.chunks(GLYPH_SPACING as usize).collect_vec();
+    let glyph = rows
+      .iter()
+      .flat_map(|row| &row[..GLYPH_WIDTH as usize])
+      .collect::<String>();
+    let separator_is_empty = rows.iter().all(|row| row[GLYPH_WIDTH as usize] == '.');
+    match FONT.iter().find(|(_, pattern)| *pattern == glyph) {
+      Some((letter, _)) if separator_is_empty => code.push(*letter),
+      _ => {
+        code.push('?');
+        let drawn = rows
+          .iter()
+          .map(|row| row.iter().collect::<String>())
+          .join("\n");
+        unrecognized.push((idx, drawn));
+      }
+    }
+  }
+  if unrecognized.is_empty() {
+    return Ok(code);
+  }
+  let glyphs = unrecognized
+    .iter()
+    .map(|(idx, drawn)| format!("Letter {}:\n{}", idx + 1, drawn))
+    .join("\n");
+  Err(format!("Unrecognized glyphs in {}\n{}", code, glyphs))
+}
+
+fn part_2(input: &str) -> Result<String, String> {
   let dots_end_idx = input.find("\n\n").unwrap();
   let parsed = parse_dots(&input[..dots_end_idx]);
   let folds = parse_folds(&input[dots_end_idx + 2..]);
@@ -122,6 +210,10 @@ fn part_2(input: &str) {
     current_line.replace_range(idx..idx + 1, "X");
   });
   println!("{}", current_line);
+
+  let code = ocr(&remaining)?;
+  println!("Code {}", code);
+  Ok(code)
 }
 
 #[test]
@@ -147,11 +239,70 @@ fn test_part_2() {
 
 fold along y=7
 fold along x=5";
-  part_2(input);
+  // The example folds into a square, which is not a letter
+  let err = part_2(input).unwrap_err();
+  assert!(err.starts_with("Unrecognized glyphs in ?"), "{}", err);
+}
+
+#[test]
+fn test_ocr() {
+  let to_dots = |rows: &[&str]| {
+    rows
+      .iter()
+      .enumerate()
+      .flat_map(|(y, row)| {
+        row
+          .chars()
+          .enumerate()
+          .filter(|(_, ch)| *ch == '#')
+          .map(move |(x, _)| Dot {
+            x: x as i32,
+            y: y as i32,
+          })
+      })
+      .collect::<HashSet<_>>()
+  };
+  let mut dots = to_dots(&[
+    "####.#..#.###..#..#..###.",
+    "#....#.#..#..#.#..#...#..",
+    "###..##...#..#.####...#..",
+    "#....#.#..###..#..#...#..",
+    "#....#.#..#.#..#..#...#..",
+    "#....#..#.#..#.#..#..###.",
+  ]);
+  assert_eq!(ocr(&dots), Ok("FKRHI".to_string()));
+
+  dots.insert(Dot { x: 6, y: 0 });
+  assert_eq!(
+    ocr(&dots),
+    Err(
+      "Unrecognized glyphs in F?RHI\nLetter 2:\n##.#.\n#.#..\n##...\n#.#..\n#.#..\n#..#."
+        .to_string()
+    )
+  );
+
+  // A stray dot between letters is not silently dropped
+  let dots = to_dots(&[
+    "#.....###",
+    "#......#.",
+    "#...#..#.",
+    "#......#.",
+    "#......#.",
+    "####..###",
+  ]);
+  assert_eq!(
+    ocr(&dots),
+    Err(
+      "Unrecognized glyphs in ?I\nLetter 1:\n#....\n#....\n#...#\n#....\n#....\n####.".to_string()
+    )
+  );
 }
 
 fn main() {
   let mut input = String::new();
   let _result = io::stdin().lock().read_to_string(&mut input);
-  part_2(&input);
+  if let Err(err) = part_2(&input) {
+    eprintln!("{}", err);
+    std::process::exit(1);
+  }
 }
@@ -1,18 +1,22 @@
-use std::io::{self, Read};
+use std::{
+  env, fs,
+  io::{self, Read},
+};
 
 use itertools::Itertools;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 struct BoardLocation {
   pub number: i32,
   pub is_marked: bool,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 struct Board {
+  pub id: usize,
   pub lines: Vec<Vec<BoardLocation>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 struct Bingo {
   pub numbers: Vec<i32>,
   pub current_round: usize,
@@ -22,7 +26,6 @@ struct Bingo {
 impl Bingo {
   fn play_next_round(&mut self) -> bool {
     if self.current_round >= self.numbers.len() {
-      println!("num boards left {}", self.boards.len());
       return false;
     }
     let number = self.numbers[self.current_round];
@@ -36,28 +39,44 @@ impl Bingo {
       }
     }
     self.current_round += 1;
-    self.current_round < self.numbers.len()
+    true
   }
 
   fn current_round_number(&self) -> i32 {
     self.numbers[self.current_round - 1]
   }
 
-  fn check_and_remove_winning_board(&mut self) -> Option<(Board, i32)> {
-    let board_idx = self
-      .boards
-      .iter()
-      .find_position(|board| is_winning_board(board))
-      .map(|(pos, board_ref)| (pos, board_ref.clone()));
+  /// Removes every board that has won, keeping them in the order they were dealt.
+  fn remove_winning_boards(&mut self) -> Vec<Board> {
+    let (winners, remaining): (Vec<Board>, Vec<Board>) =
+      self.boards.drain(..).partition(is_winning_board);
+    self.boards = remaining;
+    winners
+  }
 
-    if let Some((pos, board)) = board_idx {
-      self.boards.remove(pos);
-      return Some((board, self.current_round_number()));
+  /// Draws numbers until at least one board wins and returns all boards that won in that round.
+  fn play_until_winners(&mut self) -> Option<WinningRound> {
+    while self.play_next_round() {
+      let boards = self.remove_winning_boards();
+      if !boards.is_empty() {
+        return Some(WinningRound {
+          round: self.current_round,
+          number: self.current_round_number(),
+          boards,
+        });
+      }
     }
     None
   }
 }
 
+#[derive(Debug)]
+struct WinningRound {
+  pub round: usize,
+  pub number: i32,
+  pub boards: Vec<Board>,
+}
+
 fn is_winning_board(board: &Board) -> bool {
   let winning_line = board.lines.iter().find(|line| {
     for location in line.iter() {
@@ -89,7 +108,7 @@ fn is_winning_board(board: &Board) -> bool {
   false
 }
 
-fn parse_board(board_str: &str) -> Board {
+fn parse_board(id: usize, board_str: &str) -> Board {
   let line_iter = board_str.split('\n');
   let locations = line_iter.fold(Vec::new(), |mut acc, line| {
     if line.is_empty() {
@@ -107,24 +126,21 @@ fn parse_board(board_str: &str) -> Board {
     acc.push(line_locations);
     acc
   });
-  Board { lines: locations }
+  Board {
+    id,
+    lines: locations,
+  }
 }
 
 fn parse_game(input: &str) -> Bingo {
   let mut board_iterator = input.split("\n\n");
   let numbers: Vec<i32> = board_iterator
     .next()
-    .map(|line| {
-      line
-        .split(',')
-        .into_iter()
-        .map(|n| n.parse::<i32>().unwrap())
-        .collect()
-    })
+    .map(|line| line.split(',').map(|n| n.parse::<i32>().unwrap()).collect())
     .unwrap();
 
   let boards = board_iterator.fold(Vec::new(), |mut acc, board_string| {
-    acc.push(parse_board(board_string));
+    acc.push(parse_board(acc.len(), board_string));
     acc
   });
 
@@ -135,6 +151,460 @@ fn parse_game(input: &str) -> Bingo {
   }
 }
 
+#[derive(Debug, Clone)]
+enum Outcome {
+  Random,
+  WinsFirst(usize),
+  WinsLast(usize),
+  SimultaneousWinners(Vec<usize>),
+}
+
+#[derive(Debug, Clone)]
+struct GeneratorOptions {
+  pub num_boards: usize,
+  pub size: usize,
+  pub max_number: i32,
+  pub outcome: Outcome,
+  pub seed: u64,
+}
+
+// xorshift64*, good enough for shuffling and keeps generated files reproducible per seed
+struct Rng(u64);
+
+impl Rng {
+  fn new(seed: u64) -> Rng {
+    Rng(seed.max(1))
+  }
+
+  fn next(&mut self) -> u64 {
+    self.0 ^= self.0 >> 12;
+    self.0 ^= self.0 << 25;
+    self.0 ^= self.0 >> 27;
+    self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+  }
+
+  fn shuffle(&mut self, values: &mut [i32]) {
+    for idx in (1..values.len()).rev() {
+      let other = (self.next() % (idx as u64 + 1)) as usize;
+      values.swap(idx, other);
+    }
+  }
+
+  fn sample(&mut self, pool: &[i32], count: usize) -> Vec<i32> {
+    let mut values = pool.to_vec();
+    self.shuffle(&mut values);
+    values.truncate(count);
+    values
+  }
+}
+
+fn generate_game(options: &GeneratorOptions) -> Result<String, String> {
+  let size = options.size;
+  let cells = size * size;
+  if size == 0 || options.num_boards == 0 {
+    return Err("Need at least one board of size 1".to_owned());
+  }
+  if options.max_number < 0 {
+    return Err(format!(
+      "Largest number must not be negative, got {}",
+      options.max_number
+    ));
+  }
+  let mut rng = Rng::new(options.seed);
+  let mut draws: Vec<i32> = (0..=options.max_number).collect();
+  rng.shuffle(&mut draws);
+  if draws.len() < cells {
+    return Err(format!(
+      "Number range 0..={} is too small for {}x{} boards",
+      options.max_number, size, size
+    ));
+  }
+
+  let mut boards: Vec<Vec<i32>> = Vec::new();
+  match &options.outcome {
+    Outcome::Random => {
+      for _ in 0..options.num_boards {
+        boards.push(rng.sample(&draws, cells));
+      }
+    }
+    Outcome::WinsFirst(winner) => {
+      return generate_game(&GeneratorOptions {
+        outcome: Outcome::SimultaneousWinners(vec![*winner]),
+        ..options.clone()
+      })
+    }
+    Outcome::SimultaneousWinners(winners) => {
+      if winners.is_empty() || winners.iter().any(|idx| *idx >= options.num_boards) {
+        return Err(format!("Invalid winning boards {:?}", winners));
+      }
+      if draws.len() < size + cells {
+        return Err(format!(
+          "Number range 0..={} is too small to force winners, need at least {} numbers",
+          options.max_number,
+          size + cells
+        ));
+      }
+      // The winners share a row made of the first numbers drawn, everything else is drawn later
+      let (first_draws, rest) = draws.split_at(size);
+      for idx in 0..options.num_boards {
+        if winners.contains(&idx) {
+          let mut board = rng.sample(rest, cells - size);
+          let mut row = first_draws.to_vec();
+          rng.shuffle(&mut row);
+          let row_idx = (rng.next() % size as u64) as usize;
+          let tail = board.split_off(row_idx * size);
+          board.extend(row);
+          board.extend(tail);
+          boards.push(board);
+        } else {
+          boards.push(rng.sample(rest, cells));
+        }
+      }
+    }
+    Outcome::WinsLast(winner) => {
+      if *winner >= options.num_boards {
+        return Err(format!("Invalid winning board {}", winner));
+      }
+      if draws.len() < 2 * cells {
+        return Err(format!(
+          "Number range 0..={} is too small to force a last winner, need at least {} numbers",
+          options.max_number,
+          2 * cells
+        ));
+      }
+      // The last winner only holds the final numbers drawn, so every other board is full first
+      let (early, late) = draws.split_at(draws.len() - cells);
+      for idx in 0..options.num_boards {
+        if idx == *winner {
+          boards.push(rng.sample(late, cells));
+        } else {
+          boards.push(rng.sample(early, cells));
+        }
+      }
+    }
+  }
+
+  let width = options.max_number.to_string().len();
+  let boards_str = boards
+    .iter()
+    .map(|board| {
+      board
+        .chunks(size)
+        .map(|line| {
+          line
+            .iter()
+            .map(|number| format!("{:>width$}", number, width = width))
+            .join(" ")
+        })
+        .join("\n")
+    })
+    .join("\n\n");
+  Ok(format!("{}\n\n{}\n", draws.iter().join(","), boards_str))
+}
+
+#[derive(Debug, PartialEq)]
+enum Json {
+  Bool(bool),
+  Number(i64),
+  Array(Vec<Json>),
+  Object(Vec<(String, Json)>),
+}
+
+impl Json {
+  fn field(&self, name: &str) -> Result<&Json, String> {
+    match self {
+      Json::Object(fields) => fields
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value)
+        .ok_or_else(|| format!("Missing field '{}'", name)),
+      _ => Err(format!("Expected object with field '{}'", name)),
+    }
+  }
+
+  fn as_array(&self) -> Result<&[Json], String> {
+    match self {
+      Json::Array(values) => Ok(values),
+      _ => Err(format!("Expected array, found {:?}", self)),
+    }
+  }
+
+  fn as_i64(&self) -> Result<i64, String> {
+    match self {
+      Json::Number(n) => Ok(*n),
+      _ => Err(format!("Expected number, found {:?}", self)),
+    }
+  }
+
+  fn as_bool(&self) -> Result<bool, String> {
+    match self {
+      Json::Bool(b) => Ok(*b),
+      _ => Err(format!("Expected bool, found {:?}", self)),
+    }
+  }
+}
+
+struct JsonParser<'a> {
+  input: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> JsonParser<'a> {
+  fn parse(input: &'a str) -> Result<Json, String> {
+    let mut parser = JsonParser {
+      input: input.as_bytes(),
+      pos: 0,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos != parser.input.len() {
+      return Err(format!("Trailing characters at {}", parser.pos));
+    }
+    Ok(value)
+  }
+
+  fn skip_whitespace(&mut self) {
+    while self.pos < self.input.len() && self.input[self.pos].is_ascii_whitespace() {
+      self.pos += 1;
+    }
+  }
+
+  fn peek(&mut self) -> Option<u8> {
+    self.skip_whitespace();
+    self.input.get(self.pos).copied()
+  }
+
+  fn expect(&mut self, expected: u8) -> Result<(), String> {
+    match self.peek() {
+      Some(ch) if ch == expected => {
+        self.pos += 1;
+        Ok(())
+      }
+      _ => Err(format!("Expected '{}' at {}", expected as char, self.pos)),
+    }
+  }
+
+  fn value(&mut self) -> Result<Json, String> {
+    match self.peek() {
+      Some(b'[') => {
+        self.pos += 1;
+        let mut values = Vec::new();
+        if self.peek() == Some(b']') {
+          self.pos += 1;
+          return Ok(Json::Array(values));
+        }
+        loop {
+          values.push(self.value()?);
+          if self.peek() == Some(b',') {
+            self.pos += 1;
+          } else {
+            self.expect(b']')?;
+            return Ok(Json::Array(values));
+          }
+        }
+      }
+      Some(b'{') => {
+        self.pos += 1;
+        let mut fields = Vec::new();
+        if self.peek() == Some(b'}') {
+          self.pos += 1;
+          return Ok(Json::Object(fields));
+        }
+        loop {
+          let key = self.string()?;
+          self.expect(b':')?;
+          fields.push((key, self.value()?));
+          if self.peek() == Some(b',') {
+            self.pos += 1;
+          } else {
+            self.expect(b'}')?;
+            return Ok(Json::Object(fields));
+          }
+        }
+      }
+      Some(b't') | Some(b'f') => {
+        let rest = &self.input[self.pos..];
+        if rest.starts_with(b"true") {
+          self.pos += 4;
+          Ok(Json::Bool(true))
+        } else if rest.starts_with(b"false") {
+          self.pos += 5;
+          Ok(Json::Bool(false))
+        } else {
+          Err(format!("Invalid literal at {}", self.pos))
+        }
+      }
+      Some(b'-') | Some(b'0'..=b'9') => {
+        let start = self.pos;
+        self.pos += 1;
+        while self.pos < self.input.len() && self.input[self.pos].is_ascii_digit() {
+          self.pos += 1;
+        }
+        let number = std::str::from_utf8(&self.input[start..self.pos]).unwrap();
+        number
+          .parse::<i64>()
+          .map(Json::Number)
+          .map_err(|err| format!("Invalid number '{}': {}", number, err))
+      }
+      _ => Err(format!("Unexpected input at {}", self.pos)),
+    }
+  }
+
+  fn string(&mut self) -> Result<String, String> {
+    self.expect(b'"')?;
+    let start = self.pos;
+    while self.pos < self.input.len() && self.input[self.pos] != b'"' {
+      self.pos += 1;
+    }
+    let value = String::from_utf8_lossy(&self.input[start..self.pos]).into_owned();
+    self.expect(b'"')?;
+    Ok(value)
+  }
+}
+
+impl Bingo {
+  fn to_json(&self) -> String {
+    let numbers = self.numbers.iter().join(",");
+    let boards = self
+      .boards
+      .iter()
+      .map(|board| {
+        let lines = board
+          .lines
+          .iter()
+          .map(|line| {
+            let locations = line
+              .iter()
+              .map(|location| {
+                format!(
+                  "{{\"number\":{},\"is_marked\":{}}}",
+                  location.number, location.is_marked
+                )
+              })
+              .join(",");
+            format!("[{}]", locations)
+          })
+          .join(",");
+        format!("{{\"id\":{},\"lines\":[{}]}}", board.id, lines)
+      })
+      .join(",");
+    format!(
+      "{{\"numbers\":[{}],\"current_round\":{},\"boards\":[{}]}}",
+      numbers, self.current_round, boards
+    )
+  }
+
+  fn from_json(input: &str) -> Result<Bingo, String> {
+    let json = JsonParser::parse(input)?;
+    let numbers = json
+      .field("numbers")?
+      .as_array()?
+      .iter()
+      .map(|n| n.as_i64().and_then(to_i32))
+      .collect::<Result<Vec<_>, _>>()?;
+    let current_round = to_usize(json.field("current_round")?.as_i64()?)?;
+    if current_round > numbers.len() {
+      return Err(format!(
+        "Round {} is past the last number ({})",
+        current_round,
+        numbers.len()
+      ));
+    }
+    let boards = json
+      .field("boards")?
+      .as_array()?
+      .iter()
+      .map(|board| {
+        let lines = board
+          .field("lines")?
+          .as_array()?
+          .iter()
+          .map(|line| {
+            line
+              .as_array()?
+              .iter()
+              .map(|location| {
+                Ok(BoardLocation {
+                  number: to_i32(location.field("number")?.as_i64()?)?,
+                  is_marked: location.field("is_marked")?.as_bool()?,
+                })
+              })
+              .collect::<Result<Vec<_>, String>>()
+          })
+          .collect::<Result<Vec<_>, String>>()?;
+        let id = to_usize(board.field("id")?.as_i64()?)?;
+        // Winning is checked by rows and columns, which needs a non-empty rectangular board
+        let width = lines.first().map_or(0, Vec::len);
+        if width == 0 {
+          return Err(format!("Board {} is empty", id));
+        }
+        if lines.iter().any(|line| line.len() != width) {
+          return Err(format!("Board {} has rows of different lengths", id));
+        }
+        Ok(Board { id, lines })
+      })
+      .collect::<Result<Vec<_>, String>>()?;
+    if let Some(id) = boards.iter().map(|board| board.id).duplicates().next() {
+      return Err(format!("Board id {} is used more than once", id));
+    }
+
+    Ok(Bingo {
+      numbers,
+      current_round,
+      boards,
+    })
+  }
+}
+
+fn to_i32(n: i64) -> Result<i32, String> {
+  i32::try_from(n).map_err(|_| format!("{} does not fit in an i32", n))
+}
+
+fn to_usize(n: i64) -> Result<usize, String> {
+  usize::try_from(n).map_err(|_| format!("{} is not a valid index", n))
+}
+
+const HIGHLIGHT: &str = "\x1b[1;7m";
+const RESET: &str = "\x1b[0m";
+
+fn render_board(board: &Board) -> String {
+  board
+    .lines
+    .iter()
+    .map(|line| {
+      line
+        .iter()
+        .map(|location| {
+          if location.is_marked {
+            format!("{}{:>2}{}", HIGHLIGHT, location.number, RESET)
+          } else {
+            format!("{:>2}", location.number)
+          }
+        })
+        .join(" ")
+    })
+    .join("\n")
+}
+
+fn replay(game: &mut Bingo) {
+  while game.current_round < game.numbers.len() {
+    game.play_next_round();
+    println!(
+      "Round {}: drew {}",
+      game.current_round,
+      game.current_round_number()
+    );
+    for board in game.boards.iter() {
+      let marker = if is_winning_board(board) {
+        " (winning)"
+      } else {
+        ""
+      };
+      println!("Board {}{}\n{}\n", board.id, marker, render_board(board));
+    }
+  }
+}
+
 fn calculate_score(board: &Board, round_number: i32) -> i32 {
   let board_score = board.lines.iter().fold(0, |acc, line| {
     acc
@@ -149,14 +619,39 @@ fn calculate_score(board: &Board, round_number: i32) -> i32 {
   board_score * round_number
 }
 
-fn part_1(input: &str) {
-  let mut game = parse_game(input);
-  while game.play_next_round() && game.boards.len() > 1 {
-    while game.check_and_remove_winning_board().is_some() {}
+fn last_winning_round(game: &mut Bingo) -> Option<WinningRound> {
+  let mut last = None;
+  while let Some(winning_round) = game.play_until_winners() {
+    last = Some(winning_round);
   }
+  last
+}
+
+/// Plays at most `rounds` more rounds with `play`, leaving the remaining numbers undrawn so the
+/// game can be saved mid-way.
+fn play_rounds(game: &mut Bingo, rounds: usize, play: impl FnOnce(&mut Bingo)) {
+  let stop = game
+    .current_round
+    .saturating_add(rounds)
+    .min(game.numbers.len());
+  let undrawn = game.numbers.split_off(stop);
+  play(game);
+  game.numbers.extend(undrawn);
+}
 
-  let score = calculate_score(game.boards.first().unwrap(), game.current_round_number());
-  println!("Winning board score: {}", score);
+fn part_1(game: &mut Bingo) {
+  match last_winning_round(game) {
+    Some(winning_round) => {
+      for board in &winning_round.boards {
+        let score = calculate_score(board, winning_round.number);
+        println!(
+          "Winning board {} score: {} (round {})",
+          board.id, score, winning_round.round
+        );
+      }
+    }
+    None => println!("No board wins by round {}", game.current_round),
+  }
 }
 
 #[test]
@@ -181,12 +676,286 @@ fn test_part_1() {
   22 11 13  6  5
    2  0 12  3  7";
 
-  part_1(input);
+  part_1(&mut parse_game(input));
+
+  let last = last_winning_round(&mut parse_game(input)).unwrap();
+  assert_eq!((last.round, last.number), (15, 13));
+  assert_eq!(last.boards.iter().map(|board| board.id).collect_vec(), [1]);
+  assert_eq!(calculate_score(&last.boards[0], last.number), 1924);
+}
+
+#[test]
+fn test_simultaneous_winners() {
+  // Boards 0 and 2 both complete a line on 2, board 1 only on the final draw
+  let input = "1,2,3,4,9
+
+1 2
+5 6
+
+7 8
+9 3
+
+4 3
+1 2";
+  let mut game = parse_game(input);
+  let first = game.play_until_winners().unwrap();
+  assert_eq!(first.round, 2);
+  assert_eq!(first.number, 2);
+  assert_eq!(
+    first.boards.iter().map(|board| board.id).collect_vec(),
+    [0, 2]
+  );
+
+  let last = game.play_until_winners().unwrap();
+  assert_eq!(last.round, 5);
+  assert_eq!(last.number, 9);
+  assert_eq!(last.boards.iter().map(|board| board.id).collect_vec(), [1]);
+  assert!(game.play_until_winners().is_none());
+
+  // Every board winning on the final draw is reported together
+  let input = "5,6,1
+
+1 5
+6 2
+
+6 7
+1 8";
+  let last = last_winning_round(&mut parse_game(input)).unwrap();
+  assert_eq!(last.round, 3);
+  assert_eq!(
+    last.boards.iter().map(|board| board.id).collect_vec(),
+    [0, 1]
+  );
+  assert_eq!(calculate_score(&last.boards[0], last.number), 2);
+  assert_eq!(calculate_score(&last.boards[1], last.number), 15);
+
+  let input = "1,2
+
+1 3
+4 5";
+  assert!(last_winning_round(&mut parse_game(input)).is_none());
+}
+
+#[test]
+fn test_snapshot_round_trip() {
+  let input = "7,4,9,5,1
+
+ 1  7
+ 4 12
+
+ 9  5
+11  3";
+  let mut game = parse_game(input);
+  game.play_next_round();
+  game.play_next_round();
+
+  let snapshot = game.to_json();
+  let resumed = Bingo::from_json(&snapshot).unwrap();
+  assert_eq!(resumed, game);
+  assert!(resumed.boards[0].lines[1][0].is_marked);
+  assert!(!resumed.boards[1].lines[0][0].is_marked);
+
+  assert!(Bingo::from_json("{\"numbers\":[1],\"current_round\":2,\"boards\":[]}").is_err());
+  assert!(
+    Bingo::from_json("{\"numbers\":[4294967297],\"current_round\":0,\"boards\":[]}").is_err()
+  );
+  assert!(Bingo::from_json("{\"numbers\":[1],\"current_round\":-1,\"boards\":[]}").is_err());
+  let with_boards = |boards: &str| {
+    Bingo::from_json(&format!(
+      "{{\"numbers\":[1],\"current_round\":0,\"boards\":[{}]}}",
+      boards
+    ))
+  };
+  let location = "{\"number\":1,\"is_marked\":false}";
+  let board = |id: usize, lines: &str| format!("{{\"id\":{},\"lines\":[{}]}}", id, lines);
+  assert!(with_boards(&board(0, &format!("[{}]", location))).is_ok());
+  assert_eq!(
+    with_boards(&board(0, "")).err(),
+    Some("Board 0 is empty".to_string())
+  );
+  assert_eq!(
+    with_boards(&board(0, "[]")).err(),
+    Some("Board 0 is empty".to_string())
+  );
+  assert_eq!(
+    with_boards(&board(
+      3,
+      &format!("[{},{}],[{}]", location, location, location)
+    ))
+    .err(),
+    Some("Board 3 has rows of different lengths".to_string())
+  );
+  let single = board(2, &format!("[{}]", location));
+  assert_eq!(
+    with_boards(&format!("{},{}", single, single)).err(),
+    Some("Board id 2 is used more than once".to_string())
+  );
+
+  // Pausing after board 1 wins leaves board 0 to win once the game is resumed
+  let mut game = parse_game(input);
+  let mut first = None;
+  play_rounds(&mut game, 4, |game| first = last_winning_round(game));
+  let first = first.unwrap();
+  assert_eq!((first.round, first.boards[0].id), (4, 1));
+  assert_eq!(game.current_round, 4);
+  assert_eq!(game.numbers.len(), 5);
+
+  let mut resumed = Bingo::from_json(&game.to_json()).unwrap();
+  let last = last_winning_round(&mut resumed).unwrap();
+  assert_eq!((last.round, last.boards[0].id), (5, 0));
+}
+
+#[test]
+fn test_generate_game() {
+  // Round in which each board first wins, independent of the removal logic in part_1
+  fn win_rounds(game: &mut Bingo) -> Vec<Option<usize>> {
+    let mut rounds = vec![None; game.boards.len()];
+    while game.current_round < game.numbers.len() {
+      game.play_next_round();
+      for (idx, board) in game.boards.iter().enumerate() {
+        if rounds[idx].is_none() && is_winning_board(board) {
+          rounds[idx] = Some(game.current_round);
+        }
+      }
+    }
+    rounds
+  }
+
+  let options = GeneratorOptions {
+    num_boards: 6,
+    size: 5,
+    max_number: 99,
+    outcome: Outcome::Random,
+    seed: 2021,
+  };
+  let input = generate_game(&options).unwrap();
+  let game = parse_game(&input);
+  assert_eq!(game.numbers.len(), 100);
+  assert_eq!(game.boards.len(), 6);
+  assert!(game
+    .boards
+    .iter()
+    .all(|board| board.lines.len() == 5 && board.lines.iter().all(|line| line.len() == 5)));
+  assert_eq!(generate_game(&options).unwrap(), input);
+
+  let first = generate_game(&GeneratorOptions {
+    outcome: Outcome::WinsFirst(3),
+    ..options.clone()
+  })
+  .unwrap();
+  let rounds = win_rounds(&mut parse_game(&first));
+  assert!(rounds
+    .iter()
+    .enumerate()
+    .all(|(idx, round)| idx == 3 || round > &rounds[3]));
+
+  let last = generate_game(&GeneratorOptions {
+    outcome: Outcome::WinsLast(0),
+    ..options.clone()
+  })
+  .unwrap();
+  let rounds = win_rounds(&mut parse_game(&last));
+  assert!(rounds.iter().skip(1).all(|round| round < &rounds[0]));
+
+  let tie = generate_game(&GeneratorOptions {
+    outcome: Outcome::SimultaneousWinners(vec![1, 4]),
+    ..options.clone()
+  })
+  .unwrap();
+  let rounds = win_rounds(&mut parse_game(&tie));
+  assert_eq!(rounds[1], rounds[4]);
+  assert!([0, 2, 3, 5].iter().all(|idx| rounds[*idx] > rounds[1]));
+
+  assert_eq!(
+    generate_game(&GeneratorOptions {
+      max_number: -1,
+      ..options.clone()
+    }),
+    Err("Largest number must not be negative, got -1".to_string())
+  );
+  assert!(generate_game(&GeneratorOptions {
+    max_number: 20,
+    outcome: Outcome::WinsLast(0),
+    ..options
+  })
+  .is_err());
 }
 
 fn main() {
-  let mut input = String::new();
-  let _result = io::stdin().lock().read_to_string(&mut input);
+  let args: Vec<String> = env::args().skip(1).collect();
+  let arg_value = |name: &str| {
+    args
+      .iter()
+      .position(|arg| arg == name)
+      .and_then(|idx| args.get(idx + 1))
+  };
+
+  if args.iter().any(|arg| arg == "--generate") {
+    let number_arg = |name: &str, default: usize| {
+      arg_value(name).map_or(default, |value| value.parse::<usize>().unwrap())
+    };
+    let outcome = if let Some(idx) = arg_value("--first") {
+      Outcome::WinsFirst(idx.parse().unwrap())
+    } else if let Some(idx) = arg_value("--last") {
+      Outcome::WinsLast(idx.parse().unwrap())
+    } else if let Some(indices) = arg_value("--tie") {
+      Outcome::SimultaneousWinners(indices.split(',').map(|idx| idx.parse().unwrap()).collect())
+    } else {
+      Outcome::Random
+    };
+    let options = GeneratorOptions {
+      num_boards: number_arg("--boards", 100),
+      size: number_arg("--size", 5),
+      max_number: arg_value("--max").map_or(99, |value| {
+        value.parse::<i32>().unwrap_or_else(|err| {
+          eprintln!("Invalid --max {}: {}", value, err);
+          std::process::exit(1);
+        })
+      }),
+      outcome,
+      seed: number_arg("--seed", 1) as u64,
+    };
+    match generate_game(&options) {
+      Ok(input) => print!("{}", input),
+      Err(err) => eprintln!("{}", err),
+    }
+    return;
+  }
+
+  // --resume <file> continues a saved game instead of reading a new one from stdin
+  let mut game = match arg_value("--resume") {
+    Some(path) => {
+      let snapshot = fs::read_to_string(path).expect("Failed to read snapshot");
+      Bingo::from_json(&snapshot).unwrap_or_else(|err| {
+        eprintln!("Invalid snapshot: {}", err);
+        std::process::exit(1);
+      })
+    }
+    None => {
+      let mut input = String::new();
+      let _result = io::stdin().lock().read_to_string(&mut input);
+      parse_game(&input)
+    }
+  };
+
+  let play = |game: &mut Bingo| {
+    if args.iter().any(|arg| arg == "--replay") {
+      replay(game);
+    } else {
+      part_1(game);
+    }
+  };
+  // --rounds <n> stops after n more draws, so --save can snapshot a game in progress
+  match arg_value("--rounds") {
+    Some(rounds) => play_rounds(
+      &mut game,
+      rounds.parse().expect("Invalid round count"),
+      play,
+    ),
+    None => play(&mut game),
+  }
 
-  part_1(&input);
+  if let Some(path) = arg_value("--save") {
+    fs::write(path, game.to_json()).expect("Failed to write snapshot");
+  }
 }
@@ -1,14 +1,14 @@
-use itertools::zip;
-use num_iter::range_step_inclusive;
+use itertools::Itertools;
 use std::{
   cmp::{max, min},
-  collections::HashMap,
+  collections::{HashMap, HashSet},
+  env, fs,
   io::{self, Read},
 };
 
 use regex::Regex;
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Point {
   x: i32,
   y: i32,
@@ -40,56 +40,643 @@ impl Line {
     }
   }
 
-  fn all_points(&self) -> Vec<Point> {
-    if self.start.x == self.end.x {
-      let min = min(self.start.y, self.end.y);
-      let max = max(self.start.y, self.end.y);
-      (min..=max).map(|y| Point { x: self.start.x, y }).collect()
-    } else if self.start.y == self.end.y {
-      let min = min(self.start.x, self.end.x);
-      let max = max(self.start.x, self.end.x);
-      (min..=max).map(|x| Point { x, y: self.start.y }).collect()
+  fn kind(&self) -> LineKind {
+    let dx = self.end.x - self.start.x;
+    let dy = self.end.y - self.start.y;
+    if dx == 0 || dy == 0 {
+      LineKind::AxisAligned
+    } else if dx.abs() == dy.abs() {
+      LineKind::Diagonal
     } else {
-      let x_range = range_step_inclusive(
-        self.start.x,
-        self.end.x,
-        if self.start.x < self.end.x { 1 } else { -1 },
-      );
-      let y_range = range_step_inclusive(
-        self.start.y,
-        self.end.y,
-        if self.start.y < self.end.y { 1 } else { -1 },
-      );
-      zip(x_range, y_range).map(|(x, y)| Point { x, y }).collect()
+      LineKind::Sloped
+    }
+  }
+
+  /// Every lattice point on the line, stepping by the smallest integer vector along it.
+  fn all_points(&self) -> Vec<Point> {
+    let dx = self.end.x - self.start.x;
+    let dy = self.end.y - self.start.y;
+    let steps = gcd(dx.abs(), dy.abs());
+    if steps == 0 {
+      return vec![self.start];
+    }
+    let (step_x, step_y) = (dx / steps, dy / steps);
+    (0..=steps)
+      .map(|step| Point {
+        x: self.start.x + step * step_x,
+        y: self.start.y + step * step_y,
+      })
+      .collect()
+  }
+}
+
+fn gcd(a: i32, b: i32) -> i32 {
+  if b == 0 {
+    a
+  } else {
+    gcd(b, a % b)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LineKind {
+  AxisAligned,
+  Diagonal,
+  Sloped,
+}
+
+/// Which lines take part in the count: the puzzle's two parts are `AxisAligned` and
+/// `AxisAndDiagonal`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LineFilter {
+  AxisAligned,
+  AxisAndDiagonal,
+  All,
+}
+
+impl LineFilter {
+  fn accepts(&self, kind: LineKind) -> bool {
+    match self {
+      LineFilter::AxisAligned => kind == LineKind::AxisAligned,
+      LineFilter::AxisAndDiagonal => kind != LineKind::Sloped,
+      LineFilter::All => true,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BoundingBox {
+  min: Point,
+  max: Point,
+}
+
+impl BoundingBox {
+  fn around(lines: &[Line]) -> Option<BoundingBox> {
+    let points = lines.iter().flat_map(|line| [line.start, line.end]);
+    points.fold(None, |acc, p| {
+      Some(match acc {
+        None => BoundingBox { min: p, max: p },
+        Some(bbox) => BoundingBox {
+          min: Point {
+            x: min(bbox.min.x, p.x),
+            y: min(bbox.min.y, p.y),
+          },
+          max: Point {
+            x: max(bbox.max.x, p.x),
+            y: max(bbox.max.y, p.y),
+          },
+        },
+      })
+    })
+  }
+
+  fn width(&self) -> usize {
+    (self.max.x as i64 - self.min.x as i64 + 1) as usize
+  }
+
+  fn height(&self) -> usize {
+    (self.max.y as i64 - self.min.y as i64 + 1) as usize
+  }
+
+  fn area(&self) -> u64 {
+    self.width() as u64 * self.height() as u64
+  }
+}
+
+trait Coverage {
+  fn add(&mut self, point: &Point);
+  fn get(&self, point: &Point) -> u16;
+  /// Every covered cell with its count, in no particular order.
+  fn cells(&self) -> Box<dyn Iterator<Item = (Point, u16)> + '_>;
+}
+
+/// One counter per cell of the bounding box, for inputs where the box fits comfortably in memory.
+struct DenseCoverage {
+  bbox: BoundingBox,
+  cells: Vec<u16>,
+}
+
+impl DenseCoverage {
+  fn new(bbox: BoundingBox) -> DenseCoverage {
+    DenseCoverage {
+      bbox,
+      cells: vec![0; bbox.width() * bbox.height()],
+    }
+  }
+
+  fn index(&self, point: &Point) -> Option<usize> {
+    if point.x < self.bbox.min.x
+      || point.y < self.bbox.min.y
+      || point.x > self.bbox.max.x
+      || point.y > self.bbox.max.y
+    {
+      return None;
     }
+    let x = (point.x - self.bbox.min.x) as usize;
+    let y = (point.y - self.bbox.min.y) as usize;
+    Some(y * self.bbox.width() + x)
+  }
+}
+
+impl Coverage for DenseCoverage {
+  fn add(&mut self, point: &Point) {
+    let idx = self
+      .index(point)
+      .unwrap_or_else(|| panic!("Point {:?} outside of {:?}", point, self.bbox));
+    self.cells[idx] = self.cells[idx].saturating_add(1);
+  }
+
+  fn get(&self, point: &Point) -> u16 {
+    self.index(point).map_or(0, |idx| self.cells[idx])
+  }
+
+  fn cells(&self) -> Box<dyn Iterator<Item = (Point, u16)> + '_> {
+    let width = self.bbox.width();
+    Box::new(
+      self
+        .cells
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .map(move |(idx, count)| {
+          let point = Point {
+            x: self.bbox.min.x + (idx % width) as i32,
+            y: self.bbox.min.y + (idx / width) as i32,
+          };
+          (point, *count)
+        }),
+    )
   }
 }
 
-fn part_1(input: &str) {
-  let lines: Vec<_> = input
+const TILE_SIZE: i32 = 64;
+
+/// Dense tiles allocated on demand, for bounding boxes too large to allocate up front.
+#[derive(Default)]
+struct TiledCoverage {
+  tiles: HashMap<(i32, i32), Vec<u16>>,
+}
+
+impl TiledCoverage {
+  fn locate(point: &Point) -> ((i32, i32), usize) {
+    let tile = (point.x.div_euclid(TILE_SIZE), point.y.div_euclid(TILE_SIZE));
+    let x = point.x.rem_euclid(TILE_SIZE);
+    let y = point.y.rem_euclid(TILE_SIZE);
+    (tile, (y * TILE_SIZE + x) as usize)
+  }
+}
+
+impl Coverage for TiledCoverage {
+  fn add(&mut self, point: &Point) {
+    let (tile, idx) = TiledCoverage::locate(point);
+    let cells = self
+      .tiles
+      .entry(tile)
+      .or_insert_with(|| vec![0; (TILE_SIZE * TILE_SIZE) as usize]);
+    cells[idx] = cells[idx].saturating_add(1);
+  }
+
+  fn get(&self, point: &Point) -> u16 {
+    let (tile, idx) = TiledCoverage::locate(point);
+    self.tiles.get(&tile).map_or(0, |cells| cells[idx])
+  }
+
+  fn cells(&self) -> Box<dyn Iterator<Item = (Point, u16)> + '_> {
+    Box::new(self.tiles.iter().flat_map(|((tile_x, tile_y), cells)| {
+      cells
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .map(move |(idx, count)| {
+          let point = Point {
+            x: tile_x * TILE_SIZE + idx as i32 % TILE_SIZE,
+            y: tile_y * TILE_SIZE + idx as i32 / TILE_SIZE,
+          };
+          (point, *count)
+        })
+    }))
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Backend {
+  Auto,
+  Dense,
+  Tiled,
+}
+
+// 16M cells is 32MB of counters, beyond that only the touched tiles get allocated
+const MAX_DENSE_CELLS: u64 = 1 << 24;
+
+fn build_coverage(lines: &[Line], backend: Backend) -> Box<dyn Coverage> {
+  let bbox = BoundingBox::around(lines);
+  let use_dense = match (backend, bbox) {
+    (_, None) | (Backend::Tiled, _) => false,
+    (Backend::Dense, _) => true,
+    (Backend::Auto, Some(bbox)) => bbox.area() <= MAX_DENSE_CELLS,
+  };
+  let mut coverage: Box<dyn Coverage> = match bbox {
+    Some(bbox) if use_dense => Box::new(DenseCoverage::new(bbox)),
+    _ => Box::new(TiledCoverage::default()),
+  };
+  for line in lines {
+    for p in line.all_points() {
+      coverage.add(&p);
+    }
+  }
+  coverage
+}
+
+fn parse_lines(input: &str) -> Vec<Line> {
+  input
     .split('\n')
     .filter(|line| !line.is_empty())
-    .map(|line| Line::from_string(line))
-    .collect();
-  let covered_lines: HashMap<Point, i32> = lines.iter().fold(HashMap::new(), |mut acc, line| {
-    let all_points = line.all_points();
-    for p in all_points {
-      match acc.get(&p) {
-        Some(val) => {
-          let to_insert = val + 1;
-          acc.insert(p, to_insert);
+    .map(Line::from_string)
+    .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Orientation {
+  Horizontal,
+  Vertical,
+  Rising,
+  Falling,
+}
+
+impl Orientation {
+  // Every orientation is the family of lines a*x + b*y = key
+  fn coefficients(&self) -> (i64, i64) {
+    match self {
+      Orientation::Horizontal => (0, 1),
+      Orientation::Vertical => (1, 0),
+      Orientation::Rising => (-1, 1),
+      Orientation::Falling => (1, 1),
+    }
+  }
+
+  fn key(&self, x: i64, y: i64) -> i64 {
+    let (a, b) = self.coefficients();
+    a * x + b * y
+  }
+
+  // Position along the line, x for everything but vertical lines
+  fn param(&self, x: i64, y: i64) -> i64 {
+    match self {
+      Orientation::Vertical => y,
+      _ => x,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+  orientation: Orientation,
+  key: i64,
+  lo: i64,
+  hi: i64,
+}
+
+impl Segment {
+  fn from_line(line: &Line) -> Option<Segment> {
+    let (x1, y1) = (line.start.x as i64, line.start.y as i64);
+    let (x2, y2) = (line.end.x as i64, line.end.y as i64);
+    let orientation = if y1 == y2 {
+      Orientation::Horizontal
+    } else if x1 == x2 {
+      Orientation::Vertical
+    } else if x2 - x1 == y2 - y1 {
+      Orientation::Rising
+    } else if x2 - x1 == y1 - y2 {
+      Orientation::Falling
+    } else {
+      return None;
+    };
+    let start = orientation.param(x1, y1);
+    let end = orientation.param(x2, y2);
+    Some(Segment {
+      orientation,
+      key: orientation.key(x1, y1),
+      lo: min(start, end),
+      hi: max(start, end),
+    })
+  }
+
+  fn contains(&self, x: i64, y: i64) -> bool {
+    let param = self.orientation.param(x, y);
+    self.orientation.key(x, y) == self.key && self.lo <= param && param <= self.hi
+  }
+
+  fn x_range(&self) -> (i64, i64) {
+    match self.orientation {
+      Orientation::Vertical => (self.key, self.key),
+      _ => (self.lo, self.hi),
+    }
+  }
+
+  fn intersection(&self, other: &Segment) -> Option<(i64, i64)> {
+    let (a1, b1) = self.orientation.coefficients();
+    let (a2, b2) = other.orientation.coefficients();
+    let det = a1 * b2 - a2 * b1;
+    if det == 0 {
+      return None;
+    }
+    let x_num = self.key * b2 - other.key * b1;
+    let y_num = a1 * other.key - a2 * self.key;
+    // Rising and falling diagonals can cross between lattice points
+    if x_num % det != 0 || y_num % det != 0 {
+      return None;
+    }
+    let (x, y) = (x_num / det, y_num / det);
+    if self.contains(x, y) && other.contains(x, y) {
+      Some((x, y))
+    } else {
+      None
+    }
+  }
+}
+
+/// Counts lattice points covered by at least two lines without visiting every covered cell.
+///
+/// Collinear segments only overlap in intervals along their shared line, which are counted by
+/// length. Segments of different orientations meet in single points, found with a sweep over x.
+/// A point inside overlaps on several lines at once is counted once. Sloped lines are rejected.
+fn count_collisions_analytic(lines: &[Line]) -> Result<u64, String> {
+  let segments: Vec<Segment> = lines
+    .iter()
+    .map(|line| {
+      Segment::from_line(line).ok_or_else(|| {
+        format!(
+          "Line {},{} -> {},{} is not horizontal, vertical or 45°, the analytic mode cannot count it",
+          line.start.x, line.start.y, line.end.x, line.end.y
+        )
+      })
+    })
+    .collect::<Result<_, _>>()?;
+
+  let mut collinear: HashMap<(Orientation, i64), Vec<(i64, i64)>> = HashMap::new();
+  for segment in &segments {
+    collinear
+      .entry((segment.orientation, segment.key))
+      .or_default()
+      .push((segment.lo, segment.hi));
+  }
+  let overlaps: HashMap<(Orientation, i64), Vec<(i64, i64)>> = collinear
+    .into_iter()
+    .map(|(group, ranges)| {
+      let events = ranges
+        .iter()
+        .flat_map(|(lo, hi)| [(*lo, 1), (hi + 1, -1)])
+        .sorted();
+      let mut depth = 0;
+      let mut intervals: Vec<(i64, i64)> = Vec::new();
+      let mut overlap_start = None;
+      for (pos, delta) in events {
+        depth += delta;
+        match overlap_start {
+          None if depth > 1 => overlap_start = Some(pos),
+          Some(start) if depth <= 1 => {
+            if start < pos {
+              intervals.push((start, pos - 1));
+            }
+            overlap_start = None;
+          }
+          _ => {}
         }
-        None => {
-          acc.insert(p, 1);
+      }
+      (group, intervals)
+    })
+    .collect();
+  let in_overlap = |orientation: Orientation, x: i64, y: i64| {
+    overlaps
+      .get(&(orientation, orientation.key(x, y)))
+      .is_some_and(|intervals| {
+        let param = orientation.param(x, y);
+        let idx = intervals.partition_point(|(_, hi)| *hi < param);
+        intervals.get(idx).is_some_and(|(lo, _)| *lo <= param)
+      })
+  };
+
+  let mut crossings: HashSet<(i64, i64)> = HashSet::new();
+  let by_x = segments
+    .iter()
+    .sorted_by_key(|segment| segment.x_range().0)
+    .collect_vec();
+  let mut active: Vec<&Segment> = Vec::new();
+  for segment in by_x {
+    let (x_start, _) = segment.x_range();
+    active.retain(|other| other.x_range().1 >= x_start);
+    for other in &active {
+      if other.orientation != segment.orientation {
+        if let Some(point) = segment.intersection(other) {
+          crossings.insert(point);
         }
       }
     }
-    acc
-  });
-  let num_collisions = covered_lines
+    active.push(segment);
+  }
+
+  let overlap_cells: i64 = overlaps
+    .values()
+    .flatten()
+    .map(|(lo, hi)| hi - lo + 1)
+    .sum();
+  let correction: i64 = crossings
     .iter()
-    .fold(0, |acc, point| acc + if point.1 > &1 { 1 } else { 0 });
+    .map(|(x, y)| {
+      let overlapping = [
+        Orientation::Horizontal,
+        Orientation::Vertical,
+        Orientation::Rising,
+        Orientation::Falling,
+      ]
+      .iter()
+      .filter(|orientation| in_overlap(**orientation, *x, *y))
+      .count() as i64;
+      if overlapping == 0 {
+        1
+      } else {
+        1 - overlapping
+      }
+    })
+    .sum();
+  Ok((overlap_cells + correction) as u64)
+}
+
+/// Coverage of all lines, built once and then queried as often as needed.
+struct OverlapMap {
+  coverage: Box<dyn Coverage>,
+  bbox: Option<BoundingBox>,
+  // Number of cells covered by exactly `idx` lines
+  histogram: Vec<u64>,
+  // Summed coverage of the bounding box, one row and column larger so entry (x, y) holds the
+  // sum of all cells above and left of it. Left out when the box is too large for a dense table
+  prefix_sums: Option<Vec<u64>>,
+}
+
+impl OverlapMap {
+  fn new(lines: &[Line], backend: Backend) -> OverlapMap {
+    let coverage = build_coverage(lines, backend);
+    let mut histogram = vec![0];
+    for (_, count) in coverage.cells() {
+      let count = count as usize;
+      if count >= histogram.len() {
+        histogram.resize(count + 1, 0);
+      }
+      histogram[count] += 1;
+    }
+    let bbox = BoundingBox::around(lines);
+    let prefix_sums = bbox
+      .filter(|bbox| bbox.area() <= MAX_DENSE_CELLS)
+      .map(|bbox| {
+        let stride = bbox.width() + 1;
+        let mut sums = vec![0u64; stride * (bbox.height() + 1)];
+        for (row, y) in (bbox.min.y..=bbox.max.y).enumerate() {
+          let mut row_sum = 0;
+          for (col, x) in (bbox.min.x..=bbox.max.x).enumerate() {
+            row_sum += coverage.get(&Point { x, y }) as u64;
+            sums[(row + 1) * stride + col + 1] = sums[row * stride + col + 1] + row_sum;
+          }
+        }
+        sums
+      });
+    OverlapMap {
+      coverage,
+      bbox,
+      histogram,
+      prefix_sums,
+    }
+  }
+
+  /// Number of cells covered by `k` or more lines, never counting uncovered cells.
+  fn count_at_least(&self, k: u16) -> u64 {
+    self.histogram.iter().skip(max(k, 1) as usize).sum()
+  }
+
+  fn coverage_at(&self, point: &Point) -> u16 {
+    self.coverage.get(point)
+  }
+
+  fn max_coverage(&self) -> u16 {
+    (self.histogram.len() - 1) as u16
+  }
+
+  /// All cells with the highest coverage, ordered by row and then column.
+  fn max_coverage_cells(&self) -> Vec<Point> {
+    let max_count = self.max_coverage();
+    if max_count == 0 {
+      return Vec::new();
+    }
+    self
+      .coverage
+      .cells()
+      .filter(|(_, count)| *count == max_count)
+      .map(|(point, _)| point)
+      .sorted_by_key(|point| (point.y, point.x))
+      .collect()
+  }
+
+  /// Sum of the coverage of all cells inside the rectangle spanned by the two corners.
+  ///
+  /// Answered in constant time from the prefix sums, only maps too large for them scan the cells.
+  fn coverage_in_rect(&self, corner: &Point, opposite: &Point) -> u64 {
+    let (min_x, max_x) = (min(corner.x, opposite.x), max(corner.x, opposite.x));
+    let (min_y, max_y) = (min(corner.y, opposite.y), max(corner.y, opposite.y));
+    if let (Some(bbox), Some(sums)) = (self.bbox, &self.prefix_sums) {
+      // Clamp to the bounding box, nothing outside of it is covered
+      let (min_x, max_x) = (max(min_x, bbox.min.x), min(max_x, bbox.max.x));
+      let (min_y, max_y) = (max(min_y, bbox.min.y), min(max_y, bbox.max.y));
+      if min_x > max_x || min_y > max_y {
+        return 0;
+      }
+      let stride = bbox.width() + 1;
+      let at =
+        |x: i32, y: i32| sums[(y - bbox.min.y) as usize * stride + (x - bbox.min.x) as usize];
+      return at(max_x + 1, max_y + 1) + at(min_x, min_y)
+        - at(min_x, max_y + 1)
+        - at(max_x + 1, min_y);
+    }
+    self
+      .coverage
+      .cells()
+      .filter(|(p, _)| min_x <= p.x && p.x <= max_x && min_y <= p.y && p.y <= max_y)
+      .map(|(_, count)| count as u64)
+      .sum()
+  }
+}
+
+// Every cell of the bounding box is drawn, so refuse maps that would not fit a dense backend
+fn check_render_size(bbox: &BoundingBox) -> Result<(), String> {
+  if bbox.area() > MAX_DENSE_CELLS {
+    return Err(format!(
+      "Map of {}x{} cells is too large to render, the limit is {} cells",
+      bbox.width(),
+      bbox.height(),
+      MAX_DENSE_CELLS
+    ));
+  }
+  Ok(())
+}
+
+/// Renders the bounding box of the map like the puzzle does, `.` for uncovered cells and `#`
+/// for more than 9 lines.
+fn render_ascii(coverage: &dyn Coverage, bbox: &BoundingBox) -> Result<String, String> {
+  check_render_size(bbox)?;
+  let rows = (bbox.min.y..=bbox.max.y)
+    .map(|y| {
+      (bbox.min.x..=bbox.max.x)
+        .map(|x| match coverage.get(&Point { x, y }) {
+          0 => '.',
+          count @ 1..=9 => char::from_digit(count as u32, 10).unwrap(),
+          _ => '#',
+        })
+        .collect::<String>()
+    })
+    .join("\n");
+  Ok(rows)
+}
+
+/// Binary greyscale PGM of the bounding box where white is the most covered cell of the map.
+fn render_pgm(coverage: &dyn Coverage, bbox: &BoundingBox) -> Result<Vec<u8>, String> {
+  check_render_size(bbox)?;
+  let max_count = max(
+    coverage.cells().map(|(_, count)| count).max().unwrap_or(0),
+    1,
+  ) as u32;
+  let mut image = format!("P5\n{} {}\n255\n", bbox.width(), bbox.height()).into_bytes();
+  for y in bbox.min.y..=bbox.max.y {
+    for x in bbox.min.x..=bbox.max.x {
+      let count = coverage.get(&Point { x, y }) as u32;
+      image.push((count * 255 / max_count) as u8);
+    }
+  }
+  Ok(image)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+  Raster(Backend),
+  Analytic,
+}
+
+fn filtered_lines(input: &str, filter: LineFilter) -> Vec<Line> {
+  parse_lines(input)
+    .into_iter()
+    .filter(|line| filter.accepts(line.kind()))
+    .collect()
+}
+
+fn count_collisions(input: &str, mode: Mode, filter: LineFilter) -> Result<u64, String> {
+  let lines = filtered_lines(input, filter);
+  match mode {
+    Mode::Raster(backend) => Ok(OverlapMap::new(&lines, backend).count_at_least(2)),
+    Mode::Analytic => count_collisions_analytic(&lines),
+  }
+}
+
+fn part_1(input: &str, mode: Mode, filter: LineFilter) -> Result<u64, String> {
+  let num_collisions = count_collisions(input, mode, filter)?;
   println!("Collisions: {:?}", num_collisions);
+  Ok(num_collisions)
 }
 
 #[test]
@@ -105,12 +692,380 @@ fn test_part_1() {
 0,0 -> 8,8
 5,5 -> 8,2";
 
-  part_1(input);
+  part_1(
+    input,
+    Mode::Raster(Backend::Auto),
+    LineFilter::AxisAndDiagonal,
+  )
+  .unwrap();
+  assert_eq!(
+    count_collisions(input, Mode::Raster(Backend::Auto), LineFilter::AxisAligned),
+    Ok(5)
+  );
+  assert_eq!(
+    count_collisions(input, Mode::Analytic, LineFilter::AxisAligned),
+    Ok(5)
+  );
+  assert_eq!(
+    count_collisions(
+      input,
+      Mode::Raster(Backend::Dense),
+      LineFilter::AxisAndDiagonal
+    ),
+    Ok(12)
+  );
+  assert_eq!(
+    count_collisions(
+      input,
+      Mode::Raster(Backend::Tiled),
+      LineFilter::AxisAndDiagonal
+    ),
+    Ok(12)
+  );
+  assert_eq!(
+    count_collisions(input, Mode::Analytic, LineFilter::AxisAndDiagonal),
+    Ok(12)
+  );
+}
+
+#[test]
+fn test_backends_far_apart() {
+  // A huge bounding box with few points picks the tiled backend
+  let input = "0,0 -> 0,3
+0,2 -> 3,2
+1000000,1000000 -> 999998,1000002
+999998,1000000 -> 1000000,1000000";
+  assert_eq!(
+    count_collisions(
+      input,
+      Mode::Raster(Backend::Auto),
+      LineFilter::AxisAndDiagonal
+    ),
+    Ok(2)
+  );
+  assert_eq!(
+    count_collisions(
+      input,
+      Mode::Raster(Backend::Tiled),
+      LineFilter::AxisAndDiagonal
+    ),
+    Ok(2)
+  );
+}
+
+#[test]
+fn test_overlap_map_queries() {
+  let input = "0,9 -> 5,9
+8,0 -> 0,8
+9,4 -> 3,4
+2,2 -> 2,1
+7,0 -> 7,4
+6,4 -> 2,0
+0,9 -> 2,9
+3,4 -> 1,4
+0,0 -> 8,8
+5,5 -> 8,2";
+  let lines = filtered_lines(input, LineFilter::AxisAndDiagonal);
+  for backend in [Backend::Dense, Backend::Tiled] {
+    let map = OverlapMap::new(&lines, backend);
+    assert_eq!(map.count_at_least(0), 39);
+    assert_eq!(map.count_at_least(1), 39);
+    assert_eq!(map.count_at_least(2), 12);
+    assert_eq!(map.count_at_least(3), 2);
+    assert_eq!(map.count_at_least(4), 0);
+    assert_eq!(map.max_coverage(), 3);
+    assert_eq!(
+      map.max_coverage_cells(),
+      [Point { x: 4, y: 4 }, Point { x: 6, y: 4 }]
+    );
+    assert_eq!(map.coverage_at(&Point { x: 7, y: 1 }), 2);
+    assert_eq!(map.coverage_at(&Point { x: 100, y: -3 }), 0);
+    // Bottom row of the puzzle's map: 222111....
+    assert_eq!(
+      map.coverage_in_rect(&Point { x: 9, y: 9 }, &Point { x: 0, y: 9 }),
+      9
+    );
+    // Rows 1.2, 231 and 1.2 of the map, then rectangles reaching past it
+    assert_eq!(
+      map.coverage_in_rect(&Point { x: 3, y: 3 }, &Point { x: 5, y: 5 }),
+      12
+    );
+    assert_eq!(
+      map.coverage_in_rect(&Point { x: -5, y: -5 }, &Point { x: 20, y: 20 }),
+      53
+    );
+    assert_eq!(
+      map.coverage_in_rect(&Point { x: 10, y: 0 }, &Point { x: 20, y: 9 }),
+      0
+    );
+  }
+
+  // Maps too large for prefix sums still answer by scanning the covered cells
+  let lines = filtered_lines(
+    "0,0 -> 0,3\n0,2 -> 3,2\n1000000,1000000 -> 999998,1000002",
+    LineFilter::AxisAndDiagonal,
+  );
+  let map = OverlapMap::new(&lines, Backend::Auto);
+  assert!(map.prefix_sums.is_none());
+  assert_eq!(
+    map.coverage_in_rect(&Point { x: 0, y: 0 }, &Point { x: 1, y: 2 }),
+    5
+  );
+}
+
+#[test]
+fn test_render() {
+  let input = "0,9 -> 5,9
+8,0 -> 0,8
+9,4 -> 3,4
+2,2 -> 2,1
+7,0 -> 7,4
+6,4 -> 2,0
+0,9 -> 2,9
+3,4 -> 1,4
+0,0 -> 8,8
+5,5 -> 8,2";
+  let lines = filtered_lines(input, LineFilter::AxisAndDiagonal);
+  let bbox = BoundingBox::around(&lines).unwrap();
+  let coverage = build_coverage(&lines, Backend::Auto);
+  assert_eq!(
+    render_ascii(coverage.as_ref(), &bbox).unwrap(),
+    "1.1....11.
+.111...2..
+..2.1.111.
+...1.2.2..
+.112313211
+...1.2....
+..1...1...
+.1.....1..
+1.......1.
+222111...."
+  );
+
+  let image = render_pgm(coverage.as_ref(), &bbox).unwrap();
+  let header = b"P5\n10 10\n255\n";
+  assert_eq!(&image[..header.len()], header);
+  assert_eq!(image.len(), header.len() + 100);
+  assert_eq!(image[header.len()..].iter().max(), Some(&255));
+  assert_eq!(image[header.len() + 1], 0);
+
+  // Only the bounding box is drawn, and maps beyond the dense limit are refused
+  let lines = filtered_lines("5,3 -> 7,3\n6,2 -> 6,4", LineFilter::All);
+  let bbox = BoundingBox::around(&lines).unwrap();
+  let coverage = build_coverage(&lines, Backend::Auto);
+  assert_eq!(
+    render_ascii(coverage.as_ref(), &bbox),
+    Ok(".1.\n121\n.1.".to_string())
+  );
+  let lines = filtered_lines(
+    "0,0 -> 0,3\n1000000,1000000 -> 999998,1000002",
+    LineFilter::All,
+  );
+  let bbox = BoundingBox::around(&lines).unwrap();
+  let coverage = build_coverage(&lines, Backend::Auto);
+  assert!(render_ascii(coverage.as_ref(), &bbox).is_err());
+  assert!(render_pgm(coverage.as_ref(), &bbox).is_err());
+}
+
+#[test]
+fn test_sloped_lines() {
+  let line = Line::from_string("1,1 -> 7,10");
+  assert_eq!(line.kind(), LineKind::Sloped);
+  assert_eq!(
+    line.all_points(),
+    [(1, 1), (3, 4), (5, 7), (7, 10)].map(|(x, y)| Point { x, y })
+  );
+  assert_eq!(
+    Line::from_string("4,4 -> 4,4").all_points(),
+    [Point { x: 4, y: 4 }]
+  );
+
+  let input = "1,1 -> 7,10
+0,4 -> 9,4
+3,0 -> 3,9
+7,10 -> 7,12";
+  assert_eq!(
+    count_collisions(input, Mode::Raster(Backend::Auto), LineFilter::AxisAligned),
+    Ok(1)
+  );
+  assert_eq!(
+    count_collisions(input, Mode::Raster(Backend::Auto), LineFilter::All),
+    Ok(2)
+  );
+  assert!(count_collisions(input, Mode::Analytic, LineFilter::All).is_err());
+  assert_eq!(
+    count_collisions(input, Mode::Analytic, LineFilter::AxisAligned),
+    Ok(1)
+  );
+}
+
+#[test]
+fn test_analytic_matches_raster() {
+  let mut seed: u64 = 5;
+  let mut next = |range: i32| {
+    seed = seed
+      .wrapping_mul(6364136223846793005)
+      .wrapping_add(1442695040888963407);
+    ((seed >> 33) % range as u64) as i32
+  };
+  for _ in 0..10 {
+    let input = (0..30)
+      .map(|_| {
+        let (x, y) = (next(20), next(20));
+        let len = next(12);
+        let (dx, dy) = [(1, 0), (0, 1), (1, 1), (1, -1), (-1, 0), (0, -1)][next(6) as usize];
+        format!(
+          "{},{} -> {},{}",
+          x + 12,
+          y + 12,
+          x + 12 + dx * len,
+          y + 12 + dy * len
+        )
+      })
+      .join("\n");
+    assert_eq!(
+      count_collisions(&input, Mode::Analytic, LineFilter::AxisAndDiagonal).unwrap(),
+      count_collisions(
+        &input,
+        Mode::Raster(Backend::Dense),
+        LineFilter::AxisAndDiagonal
+      )
+      .unwrap(),
+      "{}",
+      input
+    );
+  }
+}
+
+#[test]
+fn test_analytic_huge_coordinates() {
+  let input = "0,0 -> 1000000000,0
+500000000,0 -> 1000000000,0
+700000000,5 -> 700000000,0
+0,100 -> 100,0
+600000000,0 -> 600000002,2
+600000002,0 -> 600000000,2";
+  // The overlap, the extra crossing of the falling diagonal and the crossing between the
+  // two small diagonals; the vertical line and the rising diagonal hit the overlap itself
+  assert_eq!(
+    count_collisions(input, Mode::Analytic, LineFilter::AxisAndDiagonal),
+    Ok(500000001 + 1 + 1)
+  );
 }
 
 fn main() {
   let mut input = String::new();
   let _result = io::stdin().lock().read_to_string(&mut input);
 
-  part_1(&input);
+  let args: Vec<String> = env::args().skip(1).collect();
+  let backend = match args
+    .iter()
+    .position(|arg| arg == "--backend")
+    .and_then(|idx| args.get(idx + 1))
+    .map(String::as_str)
+  {
+    None | Some("auto") => Backend::Auto,
+    Some("dense") => Backend::Dense,
+    Some("tiled") => Backend::Tiled,
+    Some(other) => panic!("Unknown backend {}", other),
+  };
+  let filter = match args
+    .iter()
+    .position(|arg| arg == "--lines")
+    .and_then(|idx| args.get(idx + 1))
+    .map(String::as_str)
+  {
+    Some("axis") => LineFilter::AxisAligned,
+    None | Some("diagonal") => LineFilter::AxisAndDiagonal,
+    Some("all") => LineFilter::All,
+    Some(other) => panic!("Unknown line filter {}", other),
+  };
+  let mode = if args.iter().any(|arg| arg == "--analytic") {
+    Mode::Analytic
+  } else {
+    Mode::Raster(backend)
+  };
+  if let Err(err) = part_1(&input, mode, filter) {
+    eprintln!("{}", err);
+    std::process::exit(1);
+  }
+
+  let arg_value = |name: &str| {
+    args
+      .iter()
+      .position(|arg| arg == name)
+      .and_then(|idx| args.get(idx + 1))
+  };
+  let parse_numbers = |value: &str| {
+    value
+      .split(',')
+      .map(|n| n.parse::<i32>().unwrap())
+      .collect_vec()
+  };
+  let ascii = args.iter().any(|arg| arg == "--ascii");
+  let show_max = args.iter().any(|arg| arg == "--max");
+  let pgm_path = arg_value("--pgm");
+  let at_least = arg_value("--at-least");
+  let at = arg_value("--at");
+  let rect = arg_value("--rect");
+  if !ascii && !show_max && [pgm_path, at_least, at, rect].iter().all(Option::is_none) {
+    return;
+  }
+
+  let lines = filtered_lines(&input, filter);
+  let map = OverlapMap::new(&lines, backend);
+  if let Some(k) = at_least {
+    let k = k.parse::<u16>().unwrap();
+    println!(
+      "Cells covered at least {} times: {}",
+      k,
+      map.count_at_least(k)
+    );
+  }
+  if show_max {
+    println!(
+      "Max coverage {} at {:?}",
+      map.max_coverage(),
+      map.max_coverage_cells()
+    );
+  }
+  if let Some(at) = at {
+    let coords = parse_numbers(at);
+    let point = Point {
+      x: coords[0],
+      y: coords[1],
+    };
+    println!("Coverage at {:?}: {}", point, map.coverage_at(&point));
+  }
+  if let Some(rect) = rect {
+    let coords = parse_numbers(rect);
+    let corner = Point {
+      x: coords[0],
+      y: coords[1],
+    };
+    let opposite = Point {
+      x: coords[2],
+      y: coords[3],
+    };
+    println!(
+      "Coverage in {:?} to {:?}: {}",
+      corner,
+      opposite,
+      map.coverage_in_rect(&corner, &opposite)
+    );
+  }
+  if let Some(bbox) = map.bbox {
+    if ascii {
+      match render_ascii(map.coverage.as_ref(), &bbox) {
+        Ok(ascii) => println!("{}", ascii),
+        Err(err) => eprintln!("{}", err),
+      }
+    }
+    if let Some(path) = pgm_path {
+      match render_pgm(map.coverage.as_ref(), &bbox) {
+        Ok(image) => fs::write(path, image).expect("Failed to write heatmap"),
+        Err(err) => eprintln!("{}", err),
+      }
+    }
+  }
 }
@@ -1,61 +1,362 @@
 use std::{
-  collections::HashMap,
+  env, fmt,
   io::{self, Read, Result},
+  ops::AddAssign,
 };
 
 use itertools::Itertools;
 
-fn make_initial_state(fishes: &[u8]) -> HashMap<u8, u64> {
-  fishes.iter().fold(HashMap::new(), |mut acc, days| {
-    match acc.get(days) {
-      Some(fish_count) => {
-        let new_fish_count = fish_count + 1;
-        acc.insert(*days, new_fish_count)
+/// How a species of fish reproduces. Lanternfish spawn every 7 days, their young need 2 more days
+/// before their first cycle, and they never die.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LifeCycle {
+  spawn_interval: usize,
+  newborn_delay: usize,
+  // Fishes die right after spawning this many times
+  max_spawns: Option<usize>,
+}
+
+impl Default for LifeCycle {
+  fn default() -> LifeCycle {
+    LifeCycle {
+      spawn_interval: 7,
+      newborn_delay: 2,
+      max_spawns: None,
+    }
+  }
+}
+
+impl LifeCycle {
+  fn num_timers(&self) -> usize {
+    self.spawn_interval + self.newborn_delay
+  }
+
+  // Fishes that have spawned a different number of times have different fates, so with mortality
+  // every spawn count gets its own set of timers
+  fn num_stages(&self) -> usize {
+    self.num_timers() * self.max_spawns.unwrap_or(1)
+  }
+
+  fn stage(&self, spawns: usize, timer: usize) -> usize {
+    spawns * self.num_timers() + timer
+  }
+
+  /// Stages that the fishes in `stage` end up in after one day, one entry per fish.
+  fn next_stages(&self, stage: usize) -> Vec<usize> {
+    let spawns = stage / self.num_timers();
+    let timer = stage % self.num_timers();
+    if timer > 0 {
+      return vec![stage - 1];
+    }
+    let newborn = self.stage(0, self.num_timers() - 1);
+    let reset_timer = self.spawn_interval - 1;
+    match self.max_spawns {
+      None => vec![self.stage(0, reset_timer), newborn],
+      Some(max_spawns) if spawns + 1 < max_spawns => {
+        vec![self.stage(spawns + 1, reset_timer), newborn]
+      }
+      Some(_) => vec![newborn],
+    }
+  }
+}
+
+fn make_initial_state(fishes: &[u8], life_cycle: &LifeCycle) -> Vec<u64> {
+  fishes
+    .iter()
+    .fold(vec![0; life_cycle.num_stages()], |mut acc, days| {
+      let days = *days as usize;
+      if days >= life_cycle.num_timers() {
+        panic!("Invalid amount of days {}", days);
+      }
+      acc[life_cycle.stage(0, days)] += 1;
+      acc
+    })
+}
+
+/// Fishes per timer value of plain lanternfish, kept in a ring buffer starting at `head`.
+struct Population {
+  counts: [u64; 9],
+  head: usize,
+}
+
+impl Population {
+  fn new(fishes: &[u8]) -> Population {
+    let mut counts = [0; 9];
+    counts.copy_from_slice(&make_initial_state(fishes, &LifeCycle::default()));
+    Population { counts, head: 0 }
+  }
+
+  fn count(&self, timer: usize) -> u64 {
+    self.counts[(self.head + timer) % 9]
+  }
+
+  /// Advances one day, or returns `None` without changing anything if a count overflows.
+  fn tick(&mut self) -> Option<()> {
+    // The spawning fishes stay where they are, which becomes the slot of the newborns, and
+    // the parents restart their cycle two slots before them
+    let spawning = self.counts[self.head];
+    let next_head = (self.head + 1) % 9;
+    let restarting = (next_head + 6) % 9;
+    self.counts[restarting] = self.counts[restarting].checked_add(spawning)?;
+    self.head = next_head;
+    Some(())
+  }
+
+  fn stages(&self) -> Vec<u64> {
+    (0..9).map(|timer| self.count(timer)).collect()
+  }
+}
+
+fn tick_stages(state: &[u64], life_cycle: &LifeCycle) -> Option<Vec<u64>> {
+  let mut next = vec![0; state.len()];
+  for (stage, count) in state.iter().enumerate() {
+    for next_stage in life_cycle.next_stages(stage) {
+      next[next_stage] = count.checked_add(next[next_stage])?;
+    }
+  }
+  Some(next)
+}
+
+fn total(stages: &[u64]) -> Option<u64> {
+  stages
+    .iter()
+    .try_fold(0u64, |acc, count| acc.checked_add(*count))
+}
+
+fn csv_row(tick: u64, stages: &[u64]) -> Option<String> {
+  Some(format!(
+    "{},{},{}",
+    tick,
+    total(stages)?,
+    stages.iter().join(",")
+  ))
+}
+
+fn csv_header(num_stages: usize) -> String {
+  let stages = (0..num_stages).map(|stage| format!("stage_{}", stage));
+  format!("tick,total,{}", stages.format(","))
+}
+
+/// Simulates day by day, optionally printing the fishes per stage of every tick as CSV.
+///
+/// Fails on the first day the population no longer fits in a `u64`, after tracing the days
+/// before it.
+fn part_1(
+  fishes: &[u8],
+  ticks: u64,
+  life_cycle: &LifeCycle,
+  trace: bool,
+) -> std::result::Result<u64, String> {
+  let overflow = |tick: u64| {
+    format!(
+      "Population overflows u64 on day {}, use the exact count instead",
+      tick
+    )
+  };
+  if trace {
+    println!("{}", csv_header(life_cycle.num_stages()));
+  }
+  // The ring buffer only models plain lanternfish, other life cycles step their stages instead
+  let mut population = (*life_cycle == LifeCycle::default()).then(|| Population::new(fishes));
+  let mut state = make_initial_state(fishes, life_cycle);
+  for tick in 1..=ticks {
+    state = match &mut population {
+      Some(population) => {
+        population.tick().ok_or_else(|| overflow(tick))?;
+        population.stages()
       }
-      None => acc.insert(*days, 1),
+      None => tick_stages(&state, life_cycle).ok_or_else(|| overflow(tick))?,
     };
-    acc
+    if trace {
+      // A total past u64 is an overflow as well, even while every stage still fits
+      println!("{}", csv_row(tick, &state).ok_or_else(|| overflow(tick))?);
+    }
+  }
+  total(&state).ok_or_else(|| overflow(ticks))
+}
+
+type Matrix = Vec<Vec<u128>>;
+
+/// One day as a linear map on the number of fishes per stage: `next = M * current`.
+fn transition_matrix(life_cycle: &LifeCycle) -> Matrix {
+  let size = life_cycle.num_stages();
+  let mut matrix = vec![vec![0; size]; size];
+  let edges = (0..size).flat_map(|stage| {
+    let next_stages = life_cycle.next_stages(stage);
+    next_stages
+      .into_iter()
+      .map(move |next_stage| (stage, next_stage))
+  });
+  for (stage, next_stage) in edges {
+    matrix[next_stage][stage] += 1;
+  }
+  matrix
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Option<Matrix> {
+  let size = a.len();
+  let mut result = vec![vec![0; size]; size];
+  for (row, result_row) in result.iter_mut().enumerate() {
+    for (col, cell) in result_row.iter_mut().enumerate() {
+      for k in 0..size {
+        *cell = a[row][k].checked_mul(b[k][col])?.checked_add(*cell)?;
+      }
+    }
+  }
+  Some(result)
+}
+
+fn identity(size: usize) -> Matrix {
+  let mut matrix = vec![vec![0; size]; size];
+  for (idx, row) in matrix.iter_mut().enumerate() {
+    row[idx] = 1;
+  }
+  matrix
+}
+
+fn matrix_power(matrix: &Matrix, exponent: u64) -> Option<Matrix> {
+  let mut result = identity(matrix.len());
+  let mut base = matrix.clone();
+  let mut exponent = exponent;
+  while exponent > 0 {
+    if exponent & 1 == 1 {
+      result = multiply(&result, &base)?;
+    }
+    exponent >>= 1;
+    if exponent > 0 {
+      base = multiply(&base, &base)?;
+    }
+  }
+  Some(result)
+}
+
+/// Number of fishes after `days` days in O(log days), or `None` if it doesn't fit in a `u128`.
+fn population_after(fishes: &[u8], days: u64, life_cycle: &LifeCycle) -> Option<u128> {
+  let mut state = vec![0u128; life_cycle.num_stages()];
+  for (stage, count) in make_initial_state(fishes, life_cycle).iter().enumerate() {
+    state[stage] = *count as u128;
+  }
+  let matrix = matrix_power(&transition_matrix(life_cycle), days)?;
+  matrix.iter().try_fold(0u128, |acc, row| {
+    row
+      .iter()
+      .zip(state.iter())
+      .try_fold(acc, |acc, (factor, count)| {
+        factor.checked_mul(*count)?.checked_add(acc)
+      })
   })
 }
 
-fn part_1(fishes: &[u8], ticks: u32) {
-  let initial_state = make_initial_state(fishes);
-  let final_state = (1..=ticks).fold(initial_state, |acc, tick| {
-    let mut next = HashMap::from([
-      (0, 0),
-      (1, 0),
-      (2, 0),
-      (3, 0),
-      (4, 0),
-      (5, 0),
-      (6, 0),
-      (7, 0),
-      (8, 0),
-    ]);
-    acc.keys().sorted().for_each(|k| match k {
-      0 => {
-        let num_fishes = acc[k];
-        next.insert(6, num_fishes);
-        next.insert(8, num_fishes);
+/// Minimal arbitrary-precision unsigned integer, only supporting what counting fishes needs.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct BigUint {
+  // Little-endian base 2^32 digits without trailing zeros
+  limbs: Vec<u32>,
+}
+
+impl BigUint {
+  fn from_u128(value: u128) -> BigUint {
+    let mut limbs = Vec::new();
+    let mut value = value;
+    while value > 0 {
+      limbs.push(value as u32);
+      value >>= 32;
+    }
+    BigUint { limbs }
+  }
+}
+
+impl AddAssign<&BigUint> for BigUint {
+  fn add_assign(&mut self, other: &BigUint) {
+    if self.limbs.len() < other.limbs.len() {
+      self.limbs.resize(other.limbs.len(), 0);
+    }
+    let mut carry = 0u64;
+    for (idx, limb) in self.limbs.iter_mut().enumerate() {
+      let sum = *limb as u64 + *other.limbs.get(idx).unwrap_or(&0) as u64 + carry;
+      *limb = sum as u32;
+      carry = sum >> 32;
+    }
+    if carry > 0 {
+      self.limbs.push(carry as u32);
+    }
+  }
+}
+
+impl fmt::Display for BigUint {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    // Peel off 9 decimal digits at a time by long division
+    let mut limbs = self.limbs.clone();
+    let mut chunks = Vec::new();
+    while !limbs.is_empty() {
+      let mut remainder = 0u64;
+      for limb in limbs.iter_mut().rev() {
+        let current = (remainder << 32) | *limb as u64;
+        *limb = (current / 1_000_000_000) as u32;
+        remainder = current % 1_000_000_000;
       }
-      7 => {
-        next.insert(6, next[&6] + acc[&7]);
+      while limbs.last() == Some(&0) {
+        limbs.pop();
       }
-      1..=8 => {
-        next.insert(k - 1, acc[k]);
+      chunks.push(remainder);
+    }
+    match chunks.split_last() {
+      None => write!(f, "0"),
+      Some((most_significant, rest)) => {
+        write!(f, "{}", most_significant)?;
+        rest
+          .iter()
+          .rev()
+          .try_for_each(|chunk| write!(f, "{:09}", chunk))
+      }
+    }
+  }
+}
+
+// Lanternfish grow by about 9% a day, so this is a few thousand digits and still fast to step
+const MAX_EXACT_DAYS: u64 = 100_000;
+
+/// Exact number of fishes after `days` days, however large, simulating one day at a time.
+///
+/// Stepping takes time linear in `days` and in the size of the answer, so it is limited to
+/// `MAX_EXACT_DAYS` days.
+fn population_exact(
+  fishes: &[u8],
+  days: u64,
+  life_cycle: &LifeCycle,
+) -> std::result::Result<BigUint, String> {
+  if days > MAX_EXACT_DAYS {
+    return Err(format!(
+      "Exact counting is limited to {} days, {} days is too many",
+      MAX_EXACT_DAYS, days
+    ));
+  }
+  let initial_state: Vec<BigUint> = make_initial_state(fishes, life_cycle)
+    .iter()
+    .map(|count| BigUint::from_u128(*count as u128))
+    .collect();
+  let final_state = (0..days).fold(initial_state, |acc, _| {
+    let mut next = vec![BigUint::default(); acc.len()];
+    for (stage, count) in acc.iter().enumerate() {
+      for next_stage in life_cycle.next_stages(stage) {
+        next[next_stage] += count;
       }
-      _ => panic!("Invalid amount of days {}", k),
-    });
-    println!("Next for tick {}: {:?}", tick, next);
+    }
     next
   });
-  println!("Final state {:?}", final_state);
-  let num_fishes: u64 = final_state.values().sum();
-  println!("Num fishes: {}", num_fishes);
+  Ok(
+    final_state
+      .iter()
+      .fold(BigUint::default(), |mut acc, count| {
+        acc += count;
+        acc
+      }),
+  )
 }
 
 fn parse_input(input: &str) -> Vec<u8> {
   input
+    .trim()
     .split(',')
     .map(|days| days.parse::<u8>().unwrap())
     .collect()
@@ -64,14 +365,183 @@ fn parse_input(input: &str) -> Vec<u8> {
 #[test]
 fn test_part_1() {
   let input = parse_input("1,4,2,4,5,3,5,2,2,5,2,1,2,4,5,2,3,5,4,3,3,1,2,3,2,1,4,4,2,1,1,4,1,4,4,4,1,4,2,4,3,3,3,3,1,1,5,4,2,5,2,4,2,2,3,1,2,5,2,4,1,5,3,5,1,4,5,3,1,4,5,2,4,5,3,1,2,5,1,2,2,1,5,5,1,1,1,4,2,5,4,3,3,1,3,4,1,1,2,2,2,5,4,4,3,2,1,1,1,1,2,5,1,3,2,1,4,4,2,1,4,5,2,5,5,3,3,1,3,2,2,3,4,1,3,1,5,4,2,5,2,4,1,5,1,4,5,1,2,4,4,1,4,1,4,4,2,2,5,4,1,3,1,3,3,1,5,1,5,5,5,1,3,1,2,1,4,5,4,4,1,3,3,1,4,1,2,1,3,2,1,5,5,3,3,1,3,5,1,5,3,5,3,1,1,1,1,4,4,3,5,5,1,1,2,2,5,5,3,2,5,2,3,4,4,1,1,2,2,4,3,5,5,1,1,5,4,3,1,3,1,2,4,4,4,4,1,4,3,4,1,3,5,5,5,1,3,5,4,3,1,3,5,4,4,3,4,2,1,1,3,1,1,2,4,1,4,1,1,1,5,5,1,3,4,1,1,5,4,4,2,2,1,3,4,4,2,2,2,3");
-  part_1(&input, 256);
+  assert_eq!(
+    part_1(&input, 256, &LifeCycle::default(), false).unwrap() as u128,
+    population_after(&input, 256, &LifeCycle::default()).unwrap()
+  );
+}
+
+#[test]
+fn test_trace() {
+  let fishes = parse_input("3,4,3,1,2");
+  let mut population = Population::new(&fishes);
+  for _ in 0..18 {
+    population.tick().unwrap();
+  }
+  assert_eq!(population.stages(), [3, 5, 3, 2, 2, 1, 5, 1, 4]);
+  assert_eq!(
+    csv_header(9),
+    "tick,total,stage_0,stage_1,stage_2,stage_3,stage_4,stage_5,stage_6,stage_7,stage_8"
+  );
+  assert_eq!(
+    csv_row(18, &population.stages()),
+    Some("18,26,3,5,3,2,2,1,5,1,4".to_string())
+  );
+  assert_eq!(csv_row(1, &[u64::MAX, 1]), None);
+
+  // Tracing stops with an error on the first day the population leaves u64, never wrapping
+  let lanternfish = LifeCycle::default();
+  let first_overflow = (0..)
+    .find(|days| population_after(&fishes, *days, &lanternfish).unwrap() > u64::MAX as u128)
+    .unwrap();
+  assert!(first_overflow > 440);
+  assert!(part_1(&fishes, first_overflow - 1, &lanternfish, true).is_ok());
+  assert_eq!(
+    part_1(&fishes, 500, &lanternfish, true),
+    Err(format!(
+      "Population overflows u64 on day {}, use the exact count instead",
+      first_overflow
+    ))
+  );
+  let faster = LifeCycle {
+    spawn_interval: 5,
+    ..lanternfish
+  };
+  assert!(part_1(&fishes, 500, &faster, true).is_err());
+}
+
+#[test]
+fn test_population_after() {
+  let fishes = parse_input("3,4,3,1,2\n");
+  let lanternfish = LifeCycle::default();
+  assert_eq!(population_after(&fishes, 0, &lanternfish), Some(5));
+  assert_eq!(population_after(&fishes, 18, &lanternfish), Some(26));
+  assert_eq!(population_after(&fishes, 80, &lanternfish), Some(5934));
+  assert_eq!(
+    population_after(&fishes, 256, &lanternfish),
+    Some(26984457539)
+  );
+  assert_eq!(
+    population_after(&fishes, 1_000_000_000_000, &lanternfish),
+    None
+  );
+}
+
+#[test]
+fn test_custom_life_cycle() {
+  let fishes = parse_input("0,2,1");
+  let life_cycles = [
+    LifeCycle {
+      spawn_interval: 3,
+      newborn_delay: 1,
+      max_spawns: None,
+    },
+    LifeCycle {
+      spawn_interval: 4,
+      newborn_delay: 0,
+      max_spawns: Some(2),
+    },
+    LifeCycle {
+      spawn_interval: 9,
+      newborn_delay: 3,
+      max_spawns: Some(3),
+    },
+  ];
+  for life_cycle in &life_cycles {
+    for days in [0, 1, 5, 17, 40] {
+      assert_eq!(
+        population_after(&fishes, days, life_cycle),
+        Some(part_1(&fishes, days, life_cycle, false).unwrap() as u128)
+      );
+    }
+  }
+
+  // A single offspring before dying keeps the population constant
+  let replacement = LifeCycle {
+    spawn_interval: 2,
+    newborn_delay: 0,
+    max_spawns: Some(1),
+  };
+  assert_eq!(
+    population_after(&parse_input("0,1,1"), 1000, &replacement),
+    Some(3)
+  );
+}
+
+#[test]
+fn test_population_exact() {
+  let fishes = parse_input("3,4,3,1,2");
+  let lanternfish = LifeCycle::default();
+  assert_eq!(
+    population_exact(&fishes, 256, &lanternfish)
+      .unwrap()
+      .to_string(),
+    "26984457539"
+  );
+  // Past what a u64 can hold, but still checkable against the u128 matrix
+  let expected = population_after(&fishes, 600, &lanternfish).unwrap();
+  assert!(expected > u64::MAX as u128);
+  assert_eq!(
+    population_exact(&fishes, 600, &lanternfish)
+      .unwrap()
+      .to_string(),
+    expected.to_string()
+  );
+
+  // Far past u128 and the exact cap the count is refused instead of stepping for ages
+  assert!(population_after(&fishes, 1_000_000_000_000, &lanternfish).is_none());
+  assert!(population_exact(&fishes, 1_000_000_000_000, &lanternfish).is_err());
+
+  let mut big = BigUint::from_u128(u128::MAX);
+  big += &BigUint::from_u128(1);
+  assert_eq!(big.to_string(), "340282366920938463463374607431768211456");
+  assert_eq!(BigUint::default().to_string(), "0");
+  assert_eq!(BigUint::from_u128(1_000_000_000).to_string(), "1000000000");
 }
 
 fn main() -> Result<()> {
   let mut input = String::new();
   let _result = io::stdin().lock().read_to_string(&mut input)?;
   let fishes = parse_input(&input);
-  part_1(&fishes, 80);
+
+  let args: Vec<String> = env::args().skip(1).collect();
+  let days = args
+    .iter()
+    .position(|arg| arg == "--days")
+    .and_then(|idx| args.get(idx + 1))
+    .map_or(80, |days| days.parse::<u64>().unwrap());
+  let arg_value = |name: &str| {
+    args
+      .iter()
+      .position(|arg| arg == name)
+      .and_then(|idx| args.get(idx + 1))
+      .map(|value| value.parse::<usize>().unwrap())
+  };
+  let defaults = LifeCycle::default();
+  let life_cycle = LifeCycle {
+    spawn_interval: arg_value("--interval").unwrap_or(defaults.spawn_interval),
+    newborn_delay: arg_value("--delay").unwrap_or(defaults.newborn_delay),
+    max_spawns: arg_value("--max-spawns"),
+  };
+  if life_cycle.spawn_interval == 0 || life_cycle.max_spawns == Some(0) {
+    panic!("Fishes need to spawn at least once, every day or less often");
+  }
+  if args.iter().any(|arg| arg == "--trace") {
+    if let Err(err) = part_1(&fishes, days, &life_cycle, true) {
+      eprintln!("{}", err);
+      std::process::exit(1);
+    }
+    return Ok(());
+  }
+  // The matrix is fast but bounded by u128, fall back to exact counting when it overflows
+  let exact = args.iter().any(|arg| arg == "--exact");
+  match population_after(&fishes, days, &life_cycle).filter(|_| !exact) {
+    Some(num_fishes) => println!("Num fishes: {}", num_fishes),
+    None => match population_exact(&fishes, days, &life_cycle) {
+      Ok(num_fishes) => println!("Num fishes: {}", num_fishes),
+      Err(err) => println!("Population overflows u128: {}", err),
+    },
+  }
 
   Ok(())
 }
@@ -1,16 +1,20 @@
-use std::cmp::{max, min};
+use std::env;
 
 use itertools::Itertools;
 
-// fn calculate_cost_1(start_pos: i32, end_pos: i32) -> i32 {
-//   i32::abs(start_pos - end_pos)
-// }
+fn calculate_cost_1(start_pos: i32, end_pos: i32) -> i64 {
+  i64::abs(start_pos as i64 - end_pos as i64)
+}
 
-fn calculate_cost_2(start_pos: i32, end_pos: i32) -> i32 {
-  let min: i32 = min(start_pos, end_pos);
-  let max = max(start_pos, end_pos);
-  let n = (max - (min - 1)) - 1;
-  ((n * (n + 1)) as f32 / 2.0) as i32
+// 1 + 2 + ... + n. Halving whichever factor is even first keeps this exact for any two i32
+// positions: the largest result is 2^63 - 2^31, which still fits an i64.
+fn calculate_cost_2(start_pos: i32, end_pos: i32) -> i64 {
+  let n = calculate_cost_1(start_pos, end_pos);
+  if n % 2 == 0 {
+    (n / 2) * (n + 1)
+  } else {
+    n * ((n + 1) / 2)
+  }
 }
 
 #[test]
@@ -19,33 +23,417 @@ fn test_calculate() {
   assert_eq!(cost, 66);
 }
 
-fn part_1(input: &str) {
-  let positions: Vec<_> = input
+#[test]
+fn test_calculate_matches_naive_sum() {
+  for n in 0..3000 {
+    let naive: i64 = (1..=n as i64).sum();
+    assert_eq!(calculate_cost_2(0, n), naive);
+    assert_eq!(calculate_cost_2(n - 1500, -1500), naive);
+  }
+  let mut seed: u64 = 11;
+  for _ in 0..200 {
+    seed = seed
+      .wrapping_mul(6364136223846793005)
+      .wrapping_add(1442695040888963407);
+    let start = (seed >> 32) as i32;
+    let end = start.saturating_add(((seed >> 8) % 100_000) as i32);
+    let n = end as i64 - start as i64;
+    assert_eq!(calculate_cost_2(start, end), (1..=n).sum::<i64>());
+  }
+  let widest = i64::MAX - (1 << 31) + 1;
+  assert_eq!(calculate_cost_2(i32::MIN, i32::MAX), widest);
+  assert_eq!(calculate_cost_2(i32::MAX, i32::MIN), widest);
+}
+
+/// Fuel a crab burns to move between two positions. Implementations must be non-decreasing and
+/// convex in the distance, which makes the total cost over all crabs convex in the target.
+trait FuelCost {
+  /// `None` when the cost doesn't fit in an i64.
+  fn cost(&self, start_pos: i32, end_pos: i32) -> Option<i64>;
+}
+
+struct Linear;
+
+impl FuelCost for Linear {
+  fn cost(&self, start_pos: i32, end_pos: i32) -> Option<i64> {
+    Some(calculate_cost_1(start_pos, end_pos))
+  }
+}
+
+struct Triangular;
+
+impl FuelCost for Triangular {
+  fn cost(&self, start_pos: i32, end_pos: i32) -> Option<i64> {
+    Some(calculate_cost_2(start_pos, end_pos))
+  }
+}
+
+/// `c0 + c1 * d + c2 * d^2 + ...` for the distance `d`.
+struct Polynomial {
+  coefficients: Vec<i64>,
+}
+
+impl Polynomial {
+  fn new(coefficients: Vec<i64>) -> Result<Polynomial, String> {
+    // Non-negative coefficients keep the cost non-decreasing and convex for positive distances
+    if coefficients.iter().any(|c| *c < 0) {
+      return Err(format!(
+        "Coefficients {:?} must not be negative",
+        coefficients
+      ));
+    }
+    Ok(Polynomial { coefficients })
+  }
+}
+
+impl FuelCost for Polynomial {
+  fn cost(&self, start_pos: i32, end_pos: i32) -> Option<i64> {
+    let distance = calculate_cost_1(start_pos, end_pos);
+    self
+      .coefficients
+      .iter()
+      .rev()
+      .try_fold(0i64, |acc, coefficient| {
+        acc.checked_mul(distance)?.checked_add(*coefficient)
+      })
+  }
+}
+
+fn parse_fuel_cost(name: &str) -> Result<Box<dyn FuelCost>, String> {
+  match name {
+    "linear" => Ok(Box::new(Linear)),
+    "triangular" => Ok(Box::new(Triangular)),
+    "quadratic" => Ok(Box::new(Polynomial::new(vec![0, 0, 1])?)),
+    _ => match name.strip_prefix("poly:") {
+      Some(coefficients) => {
+        let coefficients = coefficients
+          .split(',')
+          .map(|c| c.parse::<i64>().map_err(|err| format!("'{}': {}", c, err)))
+          .collect::<Result<Vec<_>, _>>()?;
+        Ok(Box::new(Polynomial::new(coefficients)?))
+      }
+      None => Err(format!("Unknown fuel cost '{}'", name)),
+    },
+  }
+}
+
+/// Total fuel for all crabs to reach `target`, `None` if it overflows an i64.
+fn total_cost<C>(positions: &[i32], target: i32, fuel_cost: &C) -> Option<i64>
+where
+  C: FuelCost + ?Sized,
+{
+  positions.iter().try_fold(0i64, |acc, crab_pos| {
+    acc.checked_add(fuel_cost.cost(*crab_pos, target)?)
+  })
+}
+
+// Only used to compare totals, a sum of i64 costs overflows an i128 only with 2^64 crabs
+fn wide_total_cost<C>(positions: &[i32], target: i32, fuel_cost: &C) -> Option<i128>
+where
+  C: FuelCost + ?Sized,
+{
+  positions.iter().try_fold(0i128, |acc, crab_pos| {
+    acc.checked_add(fuel_cost.cost(*crab_pos, target)? as i128)
+  })
+}
+
+/// Best position and its cost for any convex fuel cost, by binary searching for the first
+/// position where moving one step further stops being cheaper. `None` is returned if even the
+/// best position's total overflows an i64.
+///
+/// Totals are compared as i128 so an overflowing i64 total still gives a direction. If a single
+/// crab's cost overflows, the step goes towards the side with more crabs, as with a median.
+fn minimize<C>(sorted_positions: &[i32], fuel_cost: &C) -> Option<(i32, i64)>
+where
+  C: FuelCost + ?Sized,
+{
+  let mut low = *sorted_positions.first().unwrap();
+  let mut high = *sorted_positions.last().unwrap();
+  while low < high {
+    let mid = (low as i64 + high as i64).div_euclid(2) as i32;
+    let next_is_cheaper = match (
+      wide_total_cost(sorted_positions, mid + 1, fuel_cost),
+      wide_total_cost(sorted_positions, mid, fuel_cost),
+    ) {
+      (Some(next), Some(current)) => next < current,
+      (None, None) => {
+        let at_or_left = sorted_positions.partition_point(|pos| *pos <= mid);
+        sorted_positions.len() - at_or_left > at_or_left
+      }
+      (next, current) => next.is_some() && current.is_none(),
+    };
+    if next_is_cheaper {
+      low = mid + 1;
+    } else {
+      high = mid;
+    }
+  }
+  Some((low, total_cost(sorted_positions, low, fuel_cost)?))
+}
+
+/// Best position and its cost when fuel is linear in the distance: any median of the positions.
+fn optimal_linear(sorted_positions: &[i32]) -> Option<(i32, i64)> {
+  let median = sorted_positions[(sorted_positions.len() - 1) / 2];
+  Some((median, total_cost(sorted_positions, median, &Linear)?))
+}
+
+/// Best position and its cost when every step costs one more than the previous one.
+///
+/// The total cost is convex and its real minimum lies within half a step of the mean, so only
+/// the few integers around the mean need to be checked.
+fn optimal_triangular(sorted_positions: &[i32]) -> Option<(i32, i64)> {
+  let sum: i64 = sorted_positions.iter().map(|pos| *pos as i64).sum();
+  let mean = sum.div_euclid(sorted_positions.len() as i64);
+  let min_pos = *sorted_positions.first().unwrap() as i64;
+  let max_pos = *sorted_positions.last().unwrap() as i64;
+  (mean - 1..=mean + 2)
+    .filter(|pos| (min_pos..=max_pos).contains(pos))
+    .map(|pos| pos as i32)
+    .filter_map(|pos| Some((pos, total_cost(sorted_positions, pos, &Triangular)?)))
+    .min_by_key(|(pos, cost)| (*cost, *pos))
+}
+
+/// Total cost of every candidate position between the outermost crabs.
+fn cost_curve<C>(sorted_positions: &[i32], fuel_cost: &C) -> Vec<(i32, Option<i64>)>
+where
+  C: FuelCost + ?Sized,
+{
+  let min_pos = *sorted_positions.first().unwrap();
+  let max_pos = *sorted_positions.last().unwrap();
+  (min_pos..=max_pos)
+    .map(|pos| (pos, total_cost(sorted_positions, pos, fuel_cost)))
+    .collect()
+}
+
+fn format_cost(cost: Option<i64>) -> String {
+  cost.map_or("overflow".to_owned(), |cost| cost.to_string())
+}
+
+fn curve_csv(sorted_positions: &[i32]) -> String {
+  let linear = cost_curve(sorted_positions, &Linear);
+  let triangular = cost_curve(sorted_positions, &Triangular);
+  let linear_min = minimize(sorted_positions, &Linear).map(|(pos, _)| pos);
+  let triangular_min = minimize(sorted_positions, &Triangular).map(|(pos, _)| pos);
+  let rows =
+    linear
+      .iter()
+      .zip(triangular.iter())
+      .map(|((pos, linear_cost), (_, triangular_cost))| {
+        format!(
+          "{},{},{},{},{}",
+          pos,
+          format_cost(*linear_cost),
+          format_cost(*triangular_cost),
+          linear_min == Some(*pos),
+          triangular_min == Some(*pos)
+        )
+      });
+  let header = "position,linear,triangular,linear_min,triangular_min".to_owned();
+  std::iter::once(header).chain(rows).join("\n")
+}
+
+/// Horizontal bar per position, scaled so the most expensive position fills `width` columns.
+fn curve_chart(title: &str, curve: &[(i32, Option<i64>)], width: usize) -> String {
+  let max_cost = curve
+    .iter()
+    .filter_map(|(_, cost)| *cost)
+    .max()
+    .unwrap_or(0);
+  let min_cost = curve.iter().filter_map(|(_, cost)| *cost).min();
+  let label_width = curve
+    .iter()
+    .map(|(pos, _)| pos.to_string().len())
+    .max()
+    .unwrap_or(0);
+  let rows = curve.iter().map(|(pos, cost)| {
+    let bar = match cost {
+      Some(cost) if max_cost > 0 => {
+        "#".repeat((*cost as i128 * width as i128 / max_cost as i128) as usize)
+      }
+      Some(_) => String::new(),
+      None => "!".repeat(width),
+    };
+    let marker = if *cost == min_cost { " <- minimum" } else { "" };
+    format!(
+      "{:>label_width$} |{} {}{}",
+      pos,
+      bar,
+      format_cost(*cost),
+      marker,
+      label_width = label_width
+    )
+  });
+  std::iter::once(title.to_owned()).chain(rows).join("\n")
+}
+
+fn parse_positions(input: &str) -> Vec<i32> {
+  input
+    .trim()
     .split(',')
     .map(|pos| pos.parse::<i32>().unwrap())
     .sorted()
-    .collect();
-
-  let min_pos = *positions.first().unwrap();
-  let max_pos = *positions.last().unwrap();
-  let costs: Vec<_> = (min_pos..=max_pos)
-    .map(|pos| -> i32 {
-      positions
-        .iter()
-        .map(|crab_pos| calculate_cost_2(*crab_pos, pos))
-        .sum()
-    })
-    .sorted()
-    .collect();
-  println!("Lowest cost: {}", costs.first().unwrap())
+    .collect()
+}
+
+fn part_1(input: &str) {
+  let positions = parse_positions(input);
+  match optimal_linear(&positions) {
+    Some((pos, cost)) => println!("Lowest linear cost: {} at position {}", cost, pos),
+    None => println!("Linear cost overflows"),
+  }
+  match optimal_triangular(&positions) {
+    Some((pos, cost)) => println!("Lowest cost: {} at position {}", cost, pos),
+    None => println!("Triangular cost overflows"),
+  }
 }
 
 #[test]
 fn test_part_1() {
   let input = "16,1,2,0,4,2,7,1,2,14";
   part_1(input);
+  let positions = parse_positions(input);
+  assert_eq!(optimal_linear(&positions), Some((2, 37)));
+  assert_eq!(optimal_triangular(&positions), Some((5, 168)));
+}
+
+#[test]
+fn test_fuel_costs() {
+  let positions = parse_positions("16,1,2,0,4,2,7,1,2,14");
+  assert_eq!(minimize(&positions, &Linear).unwrap().1, 37);
+  assert_eq!(minimize(&positions, &Triangular), Some((5, 168)));
+  let quadratic = parse_fuel_cost("quadratic").unwrap();
+  assert_eq!(quadratic.cost(2, 7), Some(25));
+  assert_eq!(parse_fuel_cost("poly:1,2").unwrap().cost(7, 2), Some(11));
+  assert_eq!(quadratic.cost(i32::MIN, i32::MAX), None);
+  assert!(parse_fuel_cost("poly:1,-2").is_err());
+  assert!(parse_fuel_cost("cubic").is_err());
 }
+
+#[test]
+fn test_optimal_matches_scan() {
+  let mut seed: u64 = 7;
+  for _ in 0..50 {
+    let positions = (0..15)
+      .map(|_| {
+        seed = seed
+          .wrapping_mul(6364136223846793005)
+          .wrapping_add(1442695040888963407);
+        ((seed >> 33) % 300) as i32
+      })
+      .sorted()
+      .collect_vec();
+    let scan_min = |fuel_cost: &dyn FuelCost| {
+      (positions[0]..=positions[positions.len() - 1])
+        .map(|pos| total_cost(&positions, pos, fuel_cost).unwrap())
+        .min()
+    };
+    assert_eq!(optimal_linear(&positions).map(|m| m.1), scan_min(&Linear));
+    assert_eq!(
+      optimal_triangular(&positions).map(|m| m.1),
+      scan_min(&Triangular)
+    );
+    for name in ["linear", "triangular", "quadratic", "poly:3,0,2,1"] {
+      let fuel_cost = parse_fuel_cost(name).unwrap();
+      assert_eq!(
+        minimize(&positions, fuel_cost.as_ref()).map(|m| m.1),
+        scan_min(fuel_cost.as_ref())
+      );
+    }
+  }
+}
+
+#[test]
+fn test_cost_curve() {
+  let positions = parse_positions("16,1,2,0,4,2,7,1,2,14");
+  let csv = curve_csv(&positions);
+  let lines = csv.lines().collect_vec();
+  assert_eq!(lines.len(), 18);
+  assert_eq!(
+    lines[0],
+    "position,linear,triangular,linear_min,triangular_min"
+  );
+  assert_eq!(lines[1], "0,49,290,false,false");
+  assert_eq!(lines[3], "2,37,206,true,false");
+  assert_eq!(lines[6], "5,45,168,false,true");
+
+  let chart = curve_chart("Triangular", &cost_curve(&positions, &Triangular), 20);
+  let lines = chart.lines().collect_vec();
+  assert_eq!(lines[0], "Triangular");
+  assert_eq!(lines[6], " 5 |#### 168 <- minimum");
+  assert_eq!(
+    lines
+      .iter()
+      .filter(|line| line.ends_with("minimum"))
+      .count(),
+    1
+  );
+  assert!(lines[17].starts_with("16 |####################"));
+}
+
+#[test]
+fn test_total_cost_overflow() {
+  let positions = [i32::MIN, i32::MAX, i32::MAX];
+  assert_eq!(total_cost(&positions, i32::MIN, &Triangular), None);
+  assert_eq!(
+    total_cost(&positions, i32::MIN, &Linear),
+    Some(2 * (u32::MAX as i64))
+  );
+  // Far from the ends the total fits again, and that's where the minimum is
+  let (pos, cost) = minimize(&positions, &Triangular).unwrap();
+  assert!(pos > 0);
+  assert_eq!(total_cost(&positions, pos, &Triangular), Some(cost));
+  assert_eq!(optimal_triangular(&positions), Some((pos, cost)));
+  assert_eq!(
+    minimize(&positions, &Polynomial::new(vec![0, 0, 0, 1]).unwrap()),
+    None
+  );
+
+  // The first midpoints overflow on both sides, but the optimum still fits in an i64
+  let mut positions = vec![i32::MAX; 7];
+  positions.insert(0, i32::MIN);
+  let expected = Some((1610612735, 8070450532247928832));
+  assert_eq!(optimal_triangular(&positions), expected);
+  assert_eq!(minimize(&positions, &Triangular), expected);
+}
+
 fn main() {
   let input = "1101,1,29,67,1102,0,1,65,1008,65,35,66,1005,66,28,1,67,65,20,4,0,1001,65,1,65,1106,0,8,99,35,67,101,99,105,32,110,39,101,115,116,32,112,97,115,32,117,110,101,32,105,110,116,99,111,100,101,32,112,114,111,103,114,97,109,10,1425,266,740,842,335,1076,1125,108,728,131,553,757,316,361,475,1058,555,157,37,1501,287,61,22,394,886,535,235,734,1381,428,200,838,84,0,99,397,516,1260,1079,457,685,669,85,1161,851,1413,207,125,23,396,1024,637,712,942,320,507,32,686,1073,449,736,619,120,1092,674,769,519,26,42,366,187,261,389,583,170,700,695,531,57,263,1058,755,1215,413,201,617,311,443,694,285,677,722,1262,934,790,31,272,410,129,22,186,49,1040,399,19,624,132,1,35,515,423,1039,128,963,254,152,1306,33,360,484,463,483,254,741,284,14,155,6,16,1053,36,1299,637,985,470,476,383,717,304,31,209,263,70,1196,2,283,470,45,20,226,249,654,692,107,31,123,131,42,36,469,249,74,703,798,195,126,1699,135,143,1028,180,33,248,4,118,22,783,721,1033,1250,779,213,241,170,1026,0,124,709,672,349,286,494,134,361,938,985,539,267,240,951,496,431,449,242,804,422,24,202,76,947,414,396,681,142,366,342,256,978,373,677,1471,187,307,579,437,17,779,81,1380,241,69,61,758,1290,98,514,275,510,1427,185,139,816,1401,105,74,978,544,248,413,0,45,1107,223,332,723,745,71,70,330,727,261,1223,914,16,980,306,331,1011,132,70,1735,281,993,976,1,370,280,502,41,644,213,1191,518,464,693,446,44,930,1,23,1412,219,722,1028,84,552,1261,601,433,538,728,385,9,346,212,1017,7,80,88,336,480,1264,219,750,0,1080,711,1095,849,1270,175,20,314,452,620,1283,81,57,193,392,79,1330,220,396,184,922,921,902,199,56,107,32,67,275,91,202,49,4,312,372,262,49,172,493,1473,989,70,373,941,1116,798,709,865,105,442,555,1616,74,402,703,439,120,262,442,1704,1459,195,237,1763,376,734,28,867,370,6,1080,548,750,391,367,123,324,221,453,131,516,586,72,57,185,1667,468,439,225,1407,663,12,355,1320,595,60,59,158,279,365,670,505,14,240,1299,337,128,615,823,576,823,890,284,1196,717,955,1282,1002,20,176,32,222,33,248,634,885,703,543,368,585,1151,110,124,41,475,958,252,99,30,620,793,1021,540,154,635,1194,420,54,33,452,797,157,576,86,116,842,94,98,0,1162,38,483,138,949,316,1248,79,249,40,234,698,275,1239,573,649,815,348,48,78,1039,276,12,261,317,638,304,20,184,1152,711,1673,917,40,244,655,268,151,41,851,79,242,788,611,300,27,141,635,274,330,900,1023,498,269,267,46,436,844,1228,38,142,467,192,399,86,87,645,792,405,844,108,487,356,1251,332,146,128,383,1123,145,0,1148,688,127,316,579,15,215,293,73,1648,599,432,155,317,1054,205,155,451,1411,291,104,536,719,35,25,24,62,747,702,224,971,107,1210,114,41,472,29,286,4,920,0,197,135,112,308,191,1017,438,206,239,6,11,69,945,248,274,397,50,173,80,1349,268,585,590,1071,1127,351,929,106,989,396,209,691,17,149,1001,354,1296,473,179,152,141,1049,376,590,196,27,656,67,275,153,916,849,27,1093,73,156,30,1206,276,623,395,38,760,33,222,371,489,246,309,385,498,517,748,1384,1203,465,360,237,763,1173,94,431,48,770,491,132,564,84,472,1804,57,59,187,351,1340,265,1099,36,199,60,608,148,1209,1142,231,268,254,105,1020,200,1202,661,225,1313,55,808,770,80,522,185,129,36,476,815,1424,534,583,285,15,21,607,722,242,33,299,672,1253,1078,142,285,417,461,261,310,296,1934,271,144,1572,155,1039,881,1097,18,226,45,789,213,309,32,603,1102,5,81,511,672,314,7,1471,104,196,875,286,4,198,472,549,613,1453,139,596,270,164,417,709,437,27,86,758,1365,216,38,1047,124,96,255,72,67,1372,143,120,502,276,922,89,231,491,1330,245,473,25,944,266,1475,569,215,484,73,264,214,608,423,333,879,251,300,32,18,514,135,1349,80,493,569,784,2,794,846,596,30,862,318,207,546,551,1548,547,181,1219,354,650,791,53,20,629,52,105,98,312,140,111,1451,973,11,17,821,724,1836,376,82,248,86,730,1061,47,309,142,1039,114,157,26,307,1058,803,723,105,170,59,239,181,601,79,564,671,636,1465,530,533,75,261,1522,537,96,984,71,504,572,923,85,103,567,780,102,4,835,463,684,427,1091,1104,1163,626,1015,395,1881,43,490,906,1013,398,113,95,332,215,14,8,85,92,1579";
-  part_1(input);
+
+  let args: Vec<String> = env::args().skip(1).collect();
+  match args
+    .iter()
+    .position(|arg| arg == "--cost")
+    .and_then(|idx| args.get(idx + 1))
+  {
+    Some(name) => {
+      let fuel_cost = parse_fuel_cost(name).unwrap_or_else(|err| panic!("{}", err));
+      match minimize(&parse_positions(input), fuel_cost.as_ref()) {
+        Some((pos, cost)) => println!("Lowest {} cost: {} at position {}", name, cost, pos),
+        None => println!("Lowest {} cost overflows", name),
+      }
+    }
+    None => part_1(input),
+  }
+
+  let positions = parse_positions(input);
+  match args
+    .iter()
+    .position(|arg| arg == "--curve")
+    .and_then(|idx| args.get(idx + 1))
+    .map(String::as_str)
+  {
+    Some("csv") => println!("{}", curve_csv(&positions)),
+    Some("chart") => {
+      println!(
+        "{}\n",
+        curve_chart("Linear", &cost_curve(&positions, &Linear), 60)
+      );
+      println!(
+        "{}",
+        curve_chart("Triangular", &cost_curve(&positions, &Triangular), 60)
+      );
+    }
+    Some(other) => panic!("Unknown curve format {}", other),
+    None => {}
+  }
 }